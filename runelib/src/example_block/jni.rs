@@ -7,6 +7,7 @@ use jni::objects::{JByteArray, JClass, JObject};
 use jni::sys::{jbyteArray, jfloat, jint, jlong};
 use serde::{Deserialize, Serialize};
 use skia_safe::wrapper::NativeTransmutableWrapper;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use std::{fs, io};
 
@@ -84,14 +85,28 @@ pub extern "system" fn Java_rose_runeheart_Native_updateScriptContextFromRenderD
     {
         let context = RuneheartContext::from_handle_mut(context);
         if let Some(script) = render_data.active_script {
-            match context.set_active_script(SourceKind::Content(script.content.clone())) {
-                Ok(ok) => {
-                    // println!("loaded script !")
-                }
-                Err(err) => {
-                    eprintln!("{:?}", err)
+            // when the file is under the server's scripts root (single player) load it from disk
+            // so it gets hot reloaded, otherwise fall back to the contents the client sent
+            let source = if let Some(path) = context.script_path(&script.full_path) {
+                SourceKind::Path(path)
+            } else {
+                // every script in the directory is visible to `mod` declarations in the active one
+                let mut scripts: BTreeMap<String, String> = render_data
+                    .scripts
+                    .into_iter()
+                    .map(|script| (script.file_name, script.content))
+                    .collect();
+                scripts.insert(script.file_name.clone(), script.content);
+
+                SourceKind::Library {
+                    entry: script.file_name,
+                    scripts,
                 }
-            }
+            };
+
+            // this runs every tick, a failure's diagnostics are kept on the context for the ui
+            // instead of being printed each time
+            let _ = context.set_active_script(source);
         }
     }
 }
@@ -0,0 +1,54 @@
+use crate::script::capabilities::CapabilityProfile;
+use crate::script::diagnostics::ScriptDiagnostic;
+use rune::{Sources, Unit};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, LazyLock, Mutex};
+
+const UNIT_CACHE_CAPACITY: usize = 64;
+
+/// a successfully compiled script, shared between every context running the same source
+#[derive(Clone)]
+pub struct CompiledUnit {
+    pub unit: Arc<Unit>,
+    pub sources: Arc<Sources>,
+    pub warnings: Vec<ScriptDiagnostic>,
+}
+
+type CacheKey = (CapabilityProfile, u64);
+
+/// process wide cache of compiled units keyed by capability profile and source fingerprint,
+/// so placing many blocks running the same script only compiles it once
+struct UnitCache {
+    units: HashMap<CacheKey, CompiledUnit>,
+    // insertion order, the oldest unit is evicted once the cache is full
+    order: VecDeque<CacheKey>,
+}
+
+static UNIT_CACHE: LazyLock<Mutex<UnitCache>> = LazyLock::new(|| {
+    Mutex::new(UnitCache {
+        units: HashMap::new(),
+        order: VecDeque::new(),
+    })
+});
+
+pub fn get(profile: CapabilityProfile, fingerprint: u64) -> Option<CompiledUnit> {
+    let cache = UNIT_CACHE.lock().ok()?;
+    cache.units.get(&(profile, fingerprint)).cloned()
+}
+
+pub fn insert(profile: CapabilityProfile, fingerprint: u64, compiled: CompiledUnit) {
+    let Ok(mut cache) = UNIT_CACHE.lock() else {
+        return;
+    };
+
+    let key = (profile, fingerprint);
+    if cache.units.insert(key, compiled).is_none() {
+        cache.order.push_back(key);
+    }
+
+    while cache.order.len() > UNIT_CACHE_CAPACITY {
+        if let Some(oldest) = cache.order.pop_front() {
+            cache.units.remove(&oldest);
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use rune::modules::capture_io::{self, CaptureIo};
+use rune::{Context, ContextError};
+
+/// decides which rune modules player scripts get, `Sandboxed` never exposes host i/o
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum CapabilityProfile {
+    /// rune's core modules plus pure data modules (json, toml)
+    #[default]
+    Sandboxed,
+    /// additionally filesystem, process, network and time access. only available when built
+    /// with the `trusted` feature, meant for single player worlds.
+    Trusted,
+}
+
+impl CapabilityProfile {
+    pub fn is_available(self) -> bool {
+        match self {
+            CapabilityProfile::Sandboxed => true,
+            CapabilityProfile::Trusted => cfg!(feature = "trusted"),
+        }
+    }
+
+    /// script output never reaches the process stdout, `print!`/`println!`/`dbg!` write into `io`
+    pub fn context(self, io: &CaptureIo) -> Result<Context, ContextError> {
+        let mut context = Context::with_config(false)?;
+        context.install(capture_io::module(io)?)?;
+        context.install(rune_modules::json::module(false)?)?;
+        context.install(rune_modules::toml::module(false)?)?;
+
+        #[cfg(feature = "trusted")]
+        if self == CapabilityProfile::Trusted {
+            context.install(rune_modules::fs::module(false)?)?;
+            context.install(rune_modules::process::module(false)?)?;
+            context.install(rune_modules::http::module(false)?)?;
+            context.install(rune_modules::time::module(false)?)?;
+        }
+
+        Ok(context)
+    }
+}
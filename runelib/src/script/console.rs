@@ -0,0 +1,60 @@
+use rune::modules::capture_io::CaptureIo;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const DEFAULT_CONSOLE_CAPACITY: usize = 256;
+
+/// one line printed by a script through `print!`, `println!` or `dbg!`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ConsoleLine {
+    pub script: String,
+    pub tick: u64,
+    /// milliseconds since the unix epoch
+    pub timestamp: u64,
+    pub text: String,
+}
+
+/// bounded buffer of script output, the oldest lines are dropped once it is full
+pub struct Console {
+    lines: VecDeque<ConsoleLine>,
+    capacity: usize,
+}
+
+impl Console {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            lines: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// moves everything written to `io` since the last capture into the console
+    pub fn capture(&mut self, io: &CaptureIo, script: &str, tick: u64) {
+        let output = io.drain();
+        if output.is_empty() {
+            return;
+        }
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_millis() as u64);
+
+        for text in String::from_utf8_lossy(&output).lines() {
+            if self.lines.len() == self.capacity {
+                self.lines.pop_front();
+            }
+
+            self.lines.push_back(ConsoleLine {
+                script: script.to_string(),
+                tick,
+                timestamp,
+                text: text.to_string(),
+            });
+        }
+    }
+
+    pub fn drain(&mut self) -> Vec<ConsoleLine> {
+        self.lines.drain(..).collect()
+    }
+}
@@ -1,20 +1,41 @@
 use crate::script;
 use crate::script::context::RuneheartError::{
     EmptyScript, RuneAllocError, RuneBuildError, RuneContextError, RuneDiagnosticError,
-    RuneEmitError, RunePathError,
+    OutsideScriptsRoot, RunePathError, StateDeserializeError, StateSerializeError,
+    UnavailableProfile,
 };
-use crate::script::context::RuneheartExecutionError::{NoActiveScript, RuneVmError};
+use crate::script::context::RuneheartExecutionError::{
+    ArgumentAllocError, BudgetExceeded, DeadlineExceeded, NoActiveScript, NoSuchScript,
+    RuneVmError,
+};
+use crate::script::cache;
+use crate::script::cache::CompiledUnit;
+use crate::script::capabilities::CapabilityProfile;
+use crate::script::console::{Console, ConsoleLine, DEFAULT_CONSOLE_CAPACITY};
+use crate::script::diagnostics;
+use crate::script::diagnostics::{ScriptDiagnostic, ScriptError};
+use crate::script::loader::ScriptSourceLoader;
+use crate::script::rune_module::transfer::TransferQueue;
 use crate::script::rune_module::{JNIBlockContext, ScriptableBlockEntity};
+use crate::script::watcher::ScriptWatcher;
+use ciborium::{from_reader, into_writer};
 use jni::JNIEnv;
 use jni::objects::JClass;
 use jni::sys::jlong;
-use rune::diagnostics::EmitError;
-use rune::runtime::{RuntimeContext, VmError};
+use rune::runtime::{
+    Args, Generator, GeneratorState, Object, RuntimeContext, RuntimeError, ToValue, VmError,
+    VmErrorKind, VmHaltInfo, VmResult, budget,
+};
+use rune::modules::capture_io::CaptureIo;
 use rune::source::FromPathError;
-use rune::termcolor::Buffer;
 use rune::{BuildError, Context, ContextError, Diagnostics, Source, Sources, Unit, Value, Vm};
+use rune::alloc::clone::TryClone;
+use std::collections::BTreeMap;
+use std::fs;
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 #[allow(dead_code)]
 #[derive(Debug)]
@@ -23,9 +44,13 @@ pub enum RuneheartError {
     RuneContextError(ContextError),
     RuneAllocError(rune::alloc::Error),
     RuneBuildError(BuildError),
-    RuneEmitError(EmitError),
-    RuneDiagnosticError(String),
+    RuneDiagnosticError(Vec<ScriptDiagnostic>),
     RunePathError(FromPathError),
+    RuneRuntimeError(RuntimeError),
+    StateSerializeError(String),
+    StateDeserializeError(String),
+    UnavailableProfile(CapabilityProfile),
+    OutsideScriptsRoot(PathBuf),
 }
 
 #[derive(Debug)]
@@ -33,37 +58,505 @@ pub enum RuneheartError {
 pub enum RuneheartExecutionError {
     NoActiveScript,
     RuneVmError(VmError),
+    NoSuchScript(String),
+    ArgumentAllocError(rune::alloc::Error),
+    BudgetExceeded(usize),
+    DeadlineExceeded(Duration),
 }
 
 pub type RuneheartResult<T> = Result<T, RuneheartError>;
 
 pub type RuneheartExecutionResult<T> = Result<T, RuneheartExecutionError>;
 
-// TODO: our operations shoudl happen on this object not the overall context ,
+/// how much work all the scripts of a context may do in one tick before the vm is aborted.
+/// `None` means unlimited for that dimension.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExecutionBudget {
+    pub instructions: Option<usize>,
+    pub deadline: Option<Duration>,
+}
+
+impl Default for ExecutionBudget {
+    fn default() -> Self {
+        Self {
+            instructions: Some(1_000_000),
+            deadline: Some(Duration::from_millis(5)),
+        }
+    }
+}
+
+// the vm is driven in slices of this many instructions so the deadline gets checked
+// even when the instruction budget is unlimited
+const BUDGET_SLICE: usize = 10_000;
+
+// rune surfaces an exhausted budget as a `Halted` vm error instead of a separate outcome,
+// the halt happens before the instruction is executed so the execution can be resumed
+fn is_budget_halt(err: &VmError) -> bool {
+    matches!(
+        err.kind(),
+        VmErrorKind::Halted {
+            halt: VmHaltInfo::Limited
+        }
+    )
+}
+
+/// what is left of an `ExecutionBudget`. one meter is shared by every callback of a tick, so the
+/// budget bounds the whole tick instead of each script. instructions are charged a slice at a
+/// time, a callback finishing early still uses up its whole slice.
+pub struct BudgetMeter {
+    budget: ExecutionBudget,
+    started: Instant,
+    executed: usize,
+}
+
+impl BudgetMeter {
+    pub fn new(budget: ExecutionBudget) -> Self {
+        Self {
+            budget,
+            started: Instant::now(),
+            executed: 0,
+        }
+    }
+
+    fn check(&self) -> RuneheartExecutionResult<usize> {
+        if let Some(deadline) = self.budget.deadline
+            && self.started.elapsed() >= deadline
+        {
+            return Err(DeadlineExceeded(deadline));
+        }
+
+        match self.budget.instructions {
+            Some(limit) if self.executed >= limit => Err(BudgetExceeded(limit)),
+            Some(limit) => Ok(BUDGET_SLICE.min(limit - self.executed)),
+            None => Ok(BUDGET_SLICE),
+        }
+    }
+
+    /// whether nothing can run anymore this tick
+    fn exhausted(&self) -> bool {
+        self.check().is_err()
+    }
+}
+
+fn drive_with_budget<T>(
+    meter: &mut BudgetMeter,
+    mut step: impl FnMut() -> VmResult<T>,
+) -> RuneheartExecutionResult<T> {
+    loop {
+        let slice = meter.check()?;
+        let result = budget::with(slice, &mut step).call().into_result();
+        meter.executed += slice;
+
+        match result {
+            Ok(value) => return Ok(value),
+            Err(err) if is_budget_halt(&err) => continue,
+            Err(err) => return Err(RuneVmError(err)),
+        }
+    }
+}
+
+/// where the active script is between callbacks, `Suspended` means the last tick hit a `yield`
+/// and the next tick resumes it instead of calling `tick` again. `Quarantined` scripts failed
+/// too often in a row and are not ticked until re-enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptState {
+    Running,
+    Suspended,
+    Finished,
+    Quarantined,
+}
+
+/// what happens to a script that keeps failing. after each failure the script sits out
+/// `2^(failures - 1)` ticks (capped at `max_backoff_ticks`) when `backoff` is set, and after
+/// `max_consecutive_failures` failures it is quarantined.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FailurePolicy {
+    pub max_consecutive_failures: Option<u32>,
+    pub backoff: bool,
+    pub max_backoff_ticks: u32,
+}
+
+impl Default for FailurePolicy {
+    fn default() -> Self {
+        Self {
+            max_consecutive_failures: Some(10),
+            backoff: true,
+            max_backoff_ticks: 1200,
+        }
+    }
+}
+
+/// id of the script driven by the block screen through `set_active_script`
+pub const ACTIVE_SCRIPT_ID: &str = "active";
+
+/// hashes of the functions a script can export, everything except `tick` is optional
+struct Hooks {
+    tick: rune::Hash,
+    init: rune::Hash,
+    on_load: rune::Hash,
+    on_unload: rune::Hash,
+    on_error: rune::Hash,
+}
+
+impl Hooks {
+    fn new() -> Self {
+        Self {
+            tick: rune::Hash::type_hash(["tick"]),
+            init: rune::Hash::type_hash(["init"]),
+            on_load: rune::Hash::type_hash(["on_load"]),
+            on_unload: rune::Hash::type_hash(["on_unload"]),
+            on_error: rune::Hash::type_hash(["on_error"]),
+        }
+    }
+}
+
 pub struct ActiveScript {
     unit: Arc<Unit>,
+    // kept to resolve the spans of runtime errors
+    sources: Arc<Sources>,
+    // `SourceKind::fingerprint` of what this script was compiled from
+    fingerprint: u64,
     vm: Vm,
+    generator: Option<Generator>,
+    state: ScriptState,
+    enabled: bool,
+    last_error: Option<ScriptError>,
+    consecutive_failures: u32,
+    backoff_ticks: u32,
+    quarantine_reason: Option<ScriptError>,
+    // hooks that have to run before the next tick, these need a block context so they are
+    // deferred until the script is actually ticked
+    pending_init: bool,
+    pending_load: bool,
+}
+
+impl ActiveScript {
+    fn new(unit: Arc<Unit>, sources: Arc<Sources>, fingerprint: u64, vm: Vm) -> Self {
+        Self {
+            unit,
+            sources,
+            fingerprint,
+            vm,
+            generator: None,
+            state: ScriptState::Finished,
+            enabled: true,
+            last_error: None,
+            consecutive_failures: 0,
+            backoff_ticks: 0,
+            quarantine_reason: None,
+            pending_init: true,
+            pending_load: true,
+        }
+    }
+
+    pub fn state(&self) -> ScriptState {
+        self.state
+    }
+
+    pub fn last_error(&self) -> Option<&ScriptError> {
+        self.last_error.as_ref()
+    }
+
+    pub fn quarantine_reason(&self) -> Option<&ScriptError> {
+        self.quarantine_reason.as_ref()
+    }
+
+    /// whether the script should run this tick, counting down any backoff
+    fn poll_ready(&mut self) -> bool {
+        if !self.enabled || self.quarantine_reason.is_some() {
+            return false;
+        }
+
+        if self.backoff_ticks > 0 {
+            self.backoff_ticks -= 1;
+            return false;
+        }
+
+        true
+    }
+
+    fn record_outcome(&mut self, failed: bool, policy: &FailurePolicy) {
+        if !failed {
+            self.consecutive_failures = 0;
+            return;
+        }
+
+        self.consecutive_failures += 1;
+
+        if let Some(max) = policy.max_consecutive_failures
+            && self.consecutive_failures >= max
+        {
+            self.quarantine_reason = self.last_error.clone();
+            self.state = ScriptState::Quarantined;
+            return;
+        }
+
+        if policy.backoff {
+            self.backoff_ticks = 1u32
+                .checked_shl(self.consecutive_failures - 1)
+                .unwrap_or(u32::MAX)
+                .min(policy.max_backoff_ticks);
+        }
+    }
+
+    fn reenable(&mut self) {
+        self.consecutive_failures = 0;
+        self.backoff_ticks = 0;
+        self.quarantine_reason = None;
+        if self.state == ScriptState::Quarantined {
+            self.state = ScriptState::Finished;
+        }
+    }
+
+    fn has_function(&self, hash: rune::Hash) -> bool {
+        self.unit.function(&hash).is_some()
+    }
+
+    /// runs an optional lifecycle function to completion, doing nothing if the script does not
+    /// define it
+    fn call_hook(
+        &mut self,
+        hash: rune::Hash,
+        args: impl Args,
+        meter: &mut BudgetMeter,
+    ) -> RuneheartExecutionResult<()> {
+        if !self.has_function(hash) {
+            return Ok(());
+        }
+
+        let result = match self.vm.execute(hash, args) {
+            Ok(mut execution) => drive_with_budget(meter, || execution.complete()).map(|_| ()),
+            Err(err) => Err(RuneVmError(err)),
+        };
+
+        if result.is_err() {
+            self.vm.clear();
+        }
+
+        result
+    }
+
+    fn execute<C, A>(
+        &mut self,
+        hooks: &Hooks,
+        ctx: C,
+        state: &Value,
+        args: A,
+        meter: &mut BudgetMeter,
+    ) -> RuneheartExecutionResult<Value>
+    where
+        C: ToValue + Clone,
+        A: Args + ToValue,
+    {
+        if !self.enabled {
+            return Ok(Value::empty());
+        }
+
+        let result = self.run_lifecycle(hooks, ctx.clone(), state, args, meter);
+
+        // an aborted execution leaves its frames on the stack, drop them (and any suspended
+        // generator) so the next callback starts from a clean vm
+        match &result {
+            Ok(_) => self.last_error = None,
+            Err(err) => {
+                self.vm.clear();
+                self.generator = None;
+                self.state = ScriptState::Finished;
+
+                let error = ScriptError::new(err, &self.sources);
+                let message = error.to_string();
+                self.last_error = Some(error);
+
+                // whatever on_error does, the original error is what gets reported
+                let _ = self.call_hook(hooks.on_error, (ctx, message), meter);
+            }
+        }
+
+        result
+    }
+
+    fn run_lifecycle<C, A>(
+        &mut self,
+        hooks: &Hooks,
+        ctx: C,
+        state: &Value,
+        args: A,
+        meter: &mut BudgetMeter,
+    ) -> RuneheartExecutionResult<Value>
+    where
+        C: ToValue + Clone,
+        A: Args + ToValue,
+    {
+        if self.pending_init {
+            self.pending_init = false;
+            self.call_hook(hooks.init, (ctx.clone(),), meter)?;
+        }
+
+        if self.pending_load {
+            self.pending_load = false;
+            self.call_hook(hooks.on_load, (ctx, state.clone()), meter)?;
+        }
+
+        self.state = ScriptState::Running;
+        self.step(hooks.tick, args, meter)
+    }
+
+    // a `tick` containing `yield` is a generator function, calling it only hands back the
+    // generator. we keep it around and resume it on the following ticks with the fresh
+    // arguments as the value of the `yield` expression.
+    fn step<A>(
+        &mut self,
+        hash: rune::Hash,
+        args: A,
+        meter: &mut BudgetMeter,
+    ) -> RuneheartExecutionResult<Value>
+    where
+        A: Args + ToValue,
+    {
+        let (mut generator, resume_value) = match self.generator.take() {
+            Some(generator) => (
+                generator,
+                rune::to_value(args).map_err(|err| RuneVmError(err.into()))?,
+            ),
+            None => {
+                let mut execution = self.vm.execute(hash, args).map_err(RuneVmError)?;
+                let value = drive_with_budget(meter, || execution.complete())?;
+
+                match rune::from_value::<Generator>(value.clone()) {
+                    Ok(generator) => (generator, Value::empty()),
+                    Err(_) => {
+                        self.state = ScriptState::Finished;
+                        return Ok(value);
+                    }
+                }
+            }
+        };
+
+        let mut resume_value = Some(resume_value);
+        let state = drive_with_budget(meter, || {
+            generator.resume(resume_value.take().unwrap_or_else(Value::empty))
+        })?;
+
+        match state {
+            GeneratorState::Yielded(value) => {
+                self.generator = Some(generator);
+                self.state = ScriptState::Suspended;
+                Ok(value)
+            }
+            GeneratorState::Complete(value) => {
+                self.state = ScriptState::Finished;
+                Ok(value)
+            }
+        }
+    }
+}
+
+fn encode_state(states: &BTreeMap<String, Value>) -> RuneheartResult<Vec<u8>> {
+    let mut encoded = Vec::new();
+    into_writer(states, &mut encoded).map_err(|err| StateSerializeError(err.to_string()))?;
+
+    Ok(encoded)
+}
+
+fn state_fingerprint(encoded: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    encoded.hash(&mut hasher);
+    hasher.finish()
+}
+
+// the state of `id`, made empty if the script has none yet
+fn state_for(states: &mut BTreeMap<String, Value>, id: &str) -> RuneheartExecutionResult<Value> {
+    if let Some(state) = states.get(id) {
+        return Ok(state.clone());
+    }
+
+    let state = rune::to_value(Object::new()).map_err(|err| RuneVmError(err.into()))?;
+    states.insert(id.to_string(), state.clone());
+
+    Ok(state)
 }
 
 pub struct RuneheartContext {
-    tick_hash: rune::Hash,
+    hooks: Hooks,
+    profile: CapabilityProfile,
     context: Context,
     runtime: Arc<RuntimeContext>,
-    active_script: Option<ActiveScript>,
+    scripts: BTreeMap<String, ActiveScript>,
+    // removed or replaced scripts waiting for the next tick to run their `on_unload`
+    unloading: Vec<(String, ActiveScript)>,
+    budget: ExecutionBudget,
+    failure_policy: FailurePolicy,
+    // diagnostics from the last compile of each script id, kept even when the compile failed
+    diagnostics: BTreeMap<String, Vec<ScriptDiagnostic>>,
+    // fingerprint of the last source that failed to compile for each id
+    rejected: BTreeMap<String, u64>,
+    // ids loaded from disk, recompiled when their directory changes. an id is in here from its
+    // first compile on, so a file that was broken when added still gets picked up once fixed
+    paths: BTreeMap<String, PathBuf>,
+    io: CaptureIo,
+    console: Console,
+    watcher: ScriptWatcher,
+    // canonical directory `Path` scripts have to live under. script paths come from clients, so
+    // without this any player could have the server read and compile any file on the host
+    scripts_root: Option<PathBuf>,
+    // number of ticks this context has run, used to tag console output
+    ticks: u64,
+    // script-visible key/value store of each script id, lives on the context so it survives
+    // recompiles. created the first time a script runs
+    states: BTreeMap<String, Value>,
+    // fingerprint of the encoded states as of the last `take_state_changed`, so the block entity
+    // is only marked dirty when a script actually wrote to it
+    state_fingerprint: u64,
+    // moves queued by scripts with `queue_move`, flushed once at the end of each tick
+    transfers: TransferQueue,
 }
 
 pub enum SourceKind {
     Path(PathBuf),
     Content(String),
+    /// `entry` out of a set of scripts keyed by file name, `mod` declarations are resolved
+    /// against the other scripts in the set
+    Library {
+        entry: String,
+        scripts: BTreeMap<String, String>,
+    },
 }
 
 impl SourceKind {
-    pub fn into_source(self) -> RuneheartResult<Source> {
+    /// the entry source, plus a loader for its modules when they do not live on disk.
+    /// `Path` sources go through rune's default loader which resolves next to the file.
+    pub fn into_source(self) -> RuneheartResult<(Source, Option<ScriptSourceLoader>)> {
         Ok(match self {
-            SourceKind::Path(path) => Source::from_path(path).map_err(RunePathError)?,
-            SourceKind::Content(content) => Source::memory(content).map_err(RuneAllocError)?,
+            SourceKind::Path(path) => (Source::from_path(path).map_err(RunePathError)?, None),
+            SourceKind::Content(content) => {
+                (Source::memory(content).map_err(RuneAllocError)?, None)
+            }
+            SourceKind::Library { entry, scripts } => {
+                let content = scripts.get(&entry).ok_or(EmptyScript)?;
+                let source = Source::new(&entry, content).map_err(RuneAllocError)?;
+
+                (source, Some(ScriptSourceLoader::new(scripts)))
+            }
         })
     }
+
+    /// cheap identity used to skip recompiling an unchanged script. paths are fingerprinted by
+    /// the path alone, changes on disk are picked up by the watcher instead.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        match self {
+            SourceKind::Path(path) => (0u8, path).hash(&mut hasher),
+            SourceKind::Content(content) => (1u8, content).hash(&mut hasher),
+            SourceKind::Library { entry, scripts } => (2u8, entry, scripts).hash(&mut hasher),
+        }
+
+        hasher.finish()
+    }
+
+    // files on disk can change under the same fingerprint, so only in-memory sources are shared
+    fn is_cacheable(&self) -> bool {
+        !matches!(self, SourceKind::Path(_))
+    }
 }
 
 impl RuneheartContext {
@@ -75,91 +568,1021 @@ impl RuneheartContext {
         unsafe { &mut *(handle as usize as *mut RuneheartContext) }
     }
 
-    fn compile_unit(&self, source: SourceKind) -> RuneheartResult<Unit> {
+    fn compile_unit(
+        &self,
+        source: SourceKind,
+    ) -> RuneheartResult<(Unit, Sources, Vec<ScriptDiagnostic>)> {
+        let (source, mut loader) = source.into_source()?;
+
         let mut sources = Sources::new();
-        sources
-            .insert(source.into_source()?)
-            .map_err(RuneAllocError)?;
+        sources.insert(source).map_err(RuneAllocError)?;
 
         let mut diagnostics = Diagnostics::new();
 
-        let unit = rune::prepare(&mut sources)
+        let build = rune::prepare(&mut sources)
             .with_diagnostics(&mut diagnostics)
-            .with_context(&self.context)
-            .build();
+            .with_context(&self.context);
 
-        if !diagnostics.is_empty() && diagnostics.has_error() {
-            // TODO: https://docs.advntr.dev/serializer/ansi.html look into using this on kotlinm side
-            let mut writer = Buffer::no_color();
-            diagnostics
-                .emit(&mut writer, &sources)
-                .map_err(RuneEmitError)?;
+        let unit = match &mut loader {
+            Some(loader) => build.with_source_loader(loader).build(),
+            None => build.build(),
+        };
 
-            let diagnostic_data =
-                String::from_utf8(writer.into_inner()).expect("invalid utf8 from diagnostics?");
+        let collected = diagnostics::collect(&diagnostics, &sources);
 
-            return Err(RuneDiagnosticError(diagnostic_data));
+        if diagnostics.has_error() {
+            return Err(RuneDiagnosticError(collected));
         }
 
-        Ok(unit.map_err(RuneBuildError)?)
+        Ok((unit.map_err(RuneBuildError)?, sources, collected))
     }
 
-    pub fn set_active_script(&mut self, source: SourceKind) -> RuneheartResult<()> {
-        let unit = Arc::new(self.compile_unit(source)?);
-        let vm = Vm::new(self.runtime.clone(), unit.clone());
+    fn compile_cached(&self, source: SourceKind, fingerprint: u64) -> RuneheartResult<CompiledUnit> {
+        let cacheable = source.is_cacheable();
+
+        if cacheable && let Some(compiled) = cache::get(self.profile, fingerprint) {
+            return Ok(compiled);
+        }
+
+        let (unit, sources, warnings) = self.compile_unit(source)?;
+        let compiled = CompiledUnit {
+            unit: Arc::new(unit),
+            sources: Arc::new(sources),
+            warnings,
+        };
+
+        if cacheable {
+            cache::insert(self.profile, fingerprint, compiled.clone());
+        }
+
+        Ok(compiled)
+    }
+
+    /// compiles `source` and registers it under `id`, replacing any script with the same id.
+    /// a replacement keeps the enabled flag and skips `init`, but still gets `on_load`.
+    /// re-adding the source a script is already running is a no-op.
+    pub fn add_script(&mut self, id: impl Into<String>, source: SourceKind) -> RuneheartResult<()> {
+        self.load_script(id.into(), source, false)
+    }
+
+    fn load_script(&mut self, id: String, source: SourceKind, force: bool) -> RuneheartResult<()> {
+        // watcher events carry absolute paths
+        let source = match source {
+            SourceKind::Path(path) => match self.script_path(&path) {
+                Some(path) => SourceKind::Path(path),
+                None => return Err(OutsideScriptsRoot(path)),
+            },
+            source => source,
+        };
+        let path = match &source {
+            SourceKind::Path(path) => Some(path.clone()),
+            _ => None,
+        };
+
+        // watch before compiling, a file that doesn't compile yet still has to be seen changing
+        match &path {
+            Some(path) => {
+                self.watch(path);
+                self.paths.insert(id.clone(), path.clone());
+            }
+            None => {
+                self.paths.remove(&id);
+            }
+        }
 
-        self.active_script = Some(ActiveScript { unit, vm });
+        let fingerprint = source.fingerprint();
+        if !force {
+            if self
+                .scripts
+                .get(&id)
+                .is_some_and(|script| script.fingerprint == fingerprint)
+            {
+                return Ok(());
+            }
+
+            // the same broken source again, report the diagnostics we already have. a broken
+            // path is retried by `reload_changed_scripts` once its file changes, not on every add
+            if self.rejected.get(&id) == Some(&fingerprint) {
+                return Err(RuneDiagnosticError(self.diagnostics(&id).to_vec()));
+            }
+        }
+
+        let compiled = match self.compile_cached(source, fingerprint) {
+            Ok(compiled) => {
+                self.rejected.remove(&id);
+                self.diagnostics.insert(id.clone(), compiled.warnings.clone());
+                compiled
+            }
+            Err(err) => {
+                if let RuneDiagnosticError(diagnostics) = &err {
+                    self.rejected.insert(id.clone(), fingerprint);
+                    self.diagnostics.insert(id.clone(), diagnostics.clone());
+                }
+
+                return Err(err);
+            }
+        };
+        let vm = Vm::new(self.runtime.clone(), compiled.unit.clone());
+
+        let mut script = ActiveScript::new(compiled.unit, compiled.sources, fingerprint, vm);
+
+        if let Some(previous) = self.scripts.remove(&id) {
+            script.pending_init = previous.pending_init;
+            script.enabled = previous.enabled;
+            self.unloading.push((id.clone(), previous));
+        }
+
+        self.scripts.insert(id, script);
 
         Ok(())
     }
 
+    /// sets the directory scripts may be loaded from disk from, None (the default) allows none.
+    /// a root that doesn't exist counts as None
+    pub fn set_scripts_root(&mut self, root: Option<PathBuf>) {
+        self.scripts_root = root.and_then(|root| fs::canonicalize(root).ok());
+    }
+
+    /// the canonical form of `path` if it is a file under the scripts root
+    pub fn script_path(&self, path: &Path) -> Option<PathBuf> {
+        let root = self.scripts_root.as_ref()?;
+        let path = fs::canonicalize(path).ok()?;
+
+        (path.starts_with(root) && path.is_file()).then_some(path)
+    }
+
+    fn watch(&mut self, path: &Path) {
+        if let Err(err) = self.watcher.watch(path) {
+            eprintln!("failed to watch {}: {:?}", path.display(), err);
+        }
+    }
+
+    /// recompiles path-loaded scripts whose directory changed on disk. a script that fails to
+    /// compile keeps running its old unit, the new diagnostics are still recorded.
+    fn reload_changed_scripts(&mut self) {
+        let changed = self.watcher.changed_directories();
+        if changed.is_empty() {
+            return;
+        }
+
+        let reload: Vec<(String, PathBuf)> = self
+            .paths
+            .iter()
+            .filter_map(|(id, path)| {
+                changed
+                    .contains(path.parent()?)
+                    .then(|| (id.clone(), path.clone()))
+            })
+            .collect();
+
+        for (id, path) in reload {
+            // failures are already in the diagnostics for this id
+            let _ = self.load_script(id, SourceKind::Path(path), true);
+        }
+    }
+
+    pub fn diagnostics(&self, id: &str) -> &[ScriptDiagnostic] {
+        self.diagnostics.get(id).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn remove_script(&mut self, id: &str) -> bool {
+        self.diagnostics.remove(id);
+        self.rejected.remove(id);
+        self.paths.remove(id);
+
+        match self.scripts.remove(id) {
+            Some(script) => {
+                self.unloading.push((id.to_string(), script));
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn set_script_enabled(&mut self, id: &str, enabled: bool) -> RuneheartExecutionResult<()> {
+        let script = self
+            .scripts
+            .get_mut(id)
+            .ok_or_else(|| NoSuchScript(id.to_string()))?;
+        script.enabled = enabled;
+
+        Ok(())
+    }
+
+    /// lifts a quarantine and resets the failure backoff of a script
+    pub fn reenable_script(&mut self, id: &str) -> RuneheartExecutionResult<()> {
+        let script = self
+            .scripts
+            .get_mut(id)
+            .ok_or_else(|| NoSuchScript(id.to_string()))?;
+        script.reenable();
+
+        Ok(())
+    }
+
+    pub fn script(&self, id: &str) -> Option<&ActiveScript> {
+        self.scripts.get(id)
+    }
+
+    pub fn script_ids(&self) -> impl Iterator<Item = &str> {
+        self.scripts.keys().map(String::as_str)
+    }
+
+    pub fn set_active_script(&mut self, source: SourceKind) -> RuneheartResult<()> {
+        self.add_script(ACTIVE_SCRIPT_ID, source)
+    }
+
+    #[cfg(test)]
     pub fn new() -> RuneheartResult<Self> {
-        let mut context = Context::with_default_modules().map_err(RuneContextError)?;
+        Self::with_profile(CapabilityProfile::default())
+    }
+
+    pub fn with_profile(profile: CapabilityProfile) -> RuneheartResult<Self> {
+        if !profile.is_available() {
+            return Err(UnavailableProfile(profile));
+        }
+
+        let io = CaptureIo::new();
+        let mut context = profile.context(&io).map_err(RuneContextError)?;
         context
             .install(script::rune_module::module(true).map_err(RuneContextError)?)
             .map_err(RuneContextError)?;
         let runtime = Arc::new(context.runtime().map_err(RuneAllocError)?);
 
+        let states = BTreeMap::new();
+        let state_fingerprint = state_fingerprint(&encode_state(&states)?);
+
         Ok(Self {
+            profile,
             context,
             runtime,
-            tick_hash: rune::Hash::type_hash(["tick"]),
-            active_script: None,
+            hooks: Hooks::new(),
+            scripts: BTreeMap::new(),
+            unloading: Vec::new(),
+            diagnostics: BTreeMap::new(),
+            rejected: BTreeMap::new(),
+            paths: BTreeMap::new(),
+            budget: ExecutionBudget::default(),
+            failure_policy: FailurePolicy::default(),
+            io,
+            console: Console::new(DEFAULT_CONSOLE_CAPACITY),
+            watcher: ScriptWatcher::new(),
+            scripts_root: None,
+            ticks: 0,
+            states,
+            state_fingerprint,
+            transfers: TransferQueue::default(),
         })
     }
 
-    pub fn callback_tick(
+    #[cfg(test)]
+    pub fn state(&self, id: &str) -> Option<&Value> {
+        self.states.get(id)
+    }
+
+    pub fn transfers(&self) -> &TransferQueue {
+        &self.transfers
+    }
+
+    /// the states of every script, keyed by script id
+    pub fn save_state(&self) -> RuneheartResult<Vec<u8>> {
+        encode_state(&self.states)
+    }
+
+    pub fn load_state(&mut self, bytes: &[u8]) -> RuneheartResult<()> {
+        self.states = from_reader::<BTreeMap<String, Value>, _>(bytes)
+            .map_err(|err| StateDeserializeError(err.to_string()))?;
+        self.state_fingerprint = state_fingerprint(bytes);
+
+        Ok(())
+    }
+
+    /// whether the state differs from the last time this was asked, or from what was loaded
+    pub fn take_state_changed(&mut self) -> RuneheartResult<bool> {
+        let fingerprint = state_fingerprint(&self.save_state()?);
+        let changed = fingerprint != self.state_fingerprint;
+        self.state_fingerprint = fingerprint;
+
+        Ok(changed)
+    }
+
+    pub fn set_budget(&mut self, budget: ExecutionBudget) {
+        self.budget = budget;
+    }
+
+    pub fn set_failure_policy(&mut self, policy: FailurePolicy) {
+        self.failure_policy = policy;
+    }
+
+    pub fn drain_console(&mut self) -> Vec<ConsoleLine> {
+        self.console.drain()
+    }
+
+    pub fn script_state(&self, id: &str) -> Option<ScriptState> {
+        self.scripts.get(id).map(ActiveScript::state)
+    }
+
+    /// runs `on_unload` of every script waiting for it, `ctx` builds the context it gets from
+    /// the script's state. returns the ids that were unloaded
+    fn flush_unloading<C>(
+        &mut self,
+        ctx: impl Fn(Value) -> C,
+        meter: &mut BudgetMeter,
+    ) -> Vec<String>
+    where
+        C: ToValue,
+    {
+        let mut unloaded = Vec::new();
+
+        for (id, mut script) in std::mem::take(&mut self.unloading) {
+            let state = self.states.get(&id).cloned().unwrap_or_else(Value::empty);
+            let _ = script.call_hook(self.hooks.on_unload, (ctx(state),), meter);
+            self.console.capture(&self.io, &id, self.ticks);
+            unloaded.push(id);
+        }
+
+        unloaded
+    }
+
+    /// runs `on_unload` of every script, including ones still waiting for it, because the block
+    /// is going away and there won't be another tick. leaves the context without scripts
+    pub fn unload_scripts(&mut self, jni_context: JNIBlockContext) {
+        let mut meter = BudgetMeter::new(self.budget);
+        let scripts = std::mem::take(&mut self.scripts);
+        self.unloading.extend(scripts);
+        // the states stay, the block is saved with them after this
+        self.flush_unloading(|state| jni_context.with_state(state), &mut meter);
+        jni_context.flush_transfers();
+    }
+
+    // what every tick starts with: count it, pick up scripts changed on disk and run the
+    // `on_unload` of scripts removed since the last one
+    fn begin_tick<C>(&mut self, ctx: impl Fn(Value) -> C) -> BudgetMeter
+    where
+        C: ToValue,
+    {
+        let mut meter = BudgetMeter::new(self.budget);
+        self.ticks += 1;
+        self.reload_changed_scripts();
+
+        // a removed script's state goes with it once its `on_unload` ran. a replaced one keeps
+        // it, and so do ids that just weren't added yet, e.g. right after `load_state`
+        for id in self.flush_unloading(ctx, &mut meter) {
+            if !self.scripts.contains_key(&id) {
+                self.states.remove(&id);
+            }
+        }
+
+        meter
+    }
+
+    /// runs a script with its own state if it is not disabled, quarantined or backing off.
+    /// `args` builds the context and tick arguments from that state
+    fn run_script<C, A>(
+        &mut self,
+        id: &str,
+        meter: &mut BudgetMeter,
+        args: impl FnOnce(&Value) -> RuneheartExecutionResult<(C, A)>,
+    ) -> Option<RuneheartExecutionResult<Value>>
+    where
+        C: ToValue + Clone,
+        A: Args + ToValue,
+    {
+        let script = self.scripts.get_mut(id)?;
+        if !script.poll_ready() {
+            return None;
+        }
+
+        let result = state_for(&mut self.states, id).and_then(|state| {
+            let (ctx, args) = args(&state)?;
+            script.execute(&self.hooks, ctx, &state, args, meter)
+        });
+        script.record_outcome(result.is_err(), &self.failure_policy);
+        self.console.capture(&self.io, id, self.ticks);
+
+        Some(result)
+    }
+
+    // all scripts share one budget, once it is used up the rest sit this tick out. the first
+    // script to run rotates every tick so the same ones don't always miss out
+    fn run_scripts<C, A>(
+        &mut self,
+        meter: &mut BudgetMeter,
+        mut args: impl FnMut(&Value) -> RuneheartExecutionResult<(C, A)>,
+    ) -> Vec<(String, RuneheartExecutionResult<Value>)>
+    where
+        C: ToValue + Clone,
+        A: Args + ToValue,
+    {
+        let mut ids = self.script_ids().map(String::from).collect::<Vec<_>>();
+        if !ids.is_empty() {
+            let start = (self.ticks % ids.len() as u64) as usize;
+            ids.rotate_left(start);
+        }
+
+        let mut results = Vec::new();
+
+        for id in ids {
+            if meter.exhausted() {
+                break;
+            }
+
+            if let Some(result) = self.run_script(&id, meter, &mut args) {
+                results.push((id, result));
+            }
+        }
+
+        results
+    }
+
+    pub fn tick_script(
         &mut self,
+        id: &str,
         jni_context: JNIBlockContext,
         scriptables: Vec<ScriptableBlockEntity>,
     ) -> RuneheartExecutionResult<Value> {
-        match &mut self.active_script {
-            None => Ok(Value::empty()),
-            Some(script) => Ok(script
-                .vm
-                .execute(self.tick_hash, (jni_context, scriptables))
-                .map_err(RuneVmError)?
-                .complete()
-                .into_result()
-                .map_err(RuneVmError)?),
+        let mut meter = self.begin_tick(|state| jni_context.with_state(state));
+
+        if !self.scripts.contains_key(id) {
+            return Err(NoSuchScript(id.to_string()));
         }
+
+        let result = self.run_script(id, &mut meter, |state| {
+            let ctx = jni_context.with_state(state.clone());
+            Ok((ctx.clone(), (ctx, scriptables)))
+        });
+        jni_context.flush_transfers();
+
+        result.unwrap_or_else(|| Ok(Value::empty()))
+    }
+
+    /// ticks every enabled script that is not quarantined or backing off, returning the result
+    /// of each one that ran
+    pub fn callback_tick(
+        &mut self,
+        jni_context: JNIBlockContext,
+        scriptables: Vec<ScriptableBlockEntity>,
+    ) -> Vec<(String, RuneheartExecutionResult<Value>)> {
+        let mut meter = self.begin_tick(|state| jni_context.with_state(state));
+
+        let results = self.run_scripts(&mut meter, |state| {
+            let scriptables = scriptables.try_clone().map_err(ArgumentAllocError)?;
+            let ctx = jni_context.with_state(state.clone());
+            Ok((ctx.clone(), (ctx, scriptables)))
+        });
+        jni_context.flush_transfers();
+
+        results
     }
 
     #[cfg(test)]
     pub fn callback_tick_test(&mut self) -> RuneheartExecutionResult<Value> {
-        match &mut self.active_script {
-            None => Ok(Value::empty()),
-            Some(script) => Ok(script
-                .vm
-                .execute(self.tick_hash, ())
-                .map_err(RuneVmError)?
-                .complete()
-                .into_result()
-                .map_err(RuneVmError)?),
-        }
+        self.ticks += 1;
+        let mut meter = BudgetMeter::new(self.budget);
+
+        self.run_script(ACTIVE_SCRIPT_ID, &mut meter, |_| Ok(((), ())))
+            .unwrap_or_else(|| Ok(Value::empty()))
+    }
+
+    #[cfg(test)]
+    pub fn callback_tick_all_test(&mut self) -> Vec<(String, RuneheartExecutionResult<Value>)> {
+        let mut meter = self.begin_tick(|_| ());
+
+        self.run_scripts(&mut meter, |_| Ok(((), ())))
     }
 }
 
 // struct Block;
 //
 // fn move_from_to(Block, );
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script::diagnostics::DiagnosticSeverity;
+
+    #[test]
+    fn test_budget_exceeded() {
+        let mut context = RuneheartContext::new().unwrap();
+        context.set_budget(ExecutionBudget {
+            instructions: Some(50_000),
+            deadline: None,
+        });
+        context
+            .set_active_script(SourceKind::Content("pub fn tick() { loop {} }".to_string()))
+            .unwrap();
+
+        assert!(matches!(
+            context.callback_tick_test(),
+            Err(BudgetExceeded(50_000))
+        ));
+    }
+
+    #[test]
+    fn test_error_mentioning_limited_is_not_a_budget_halt() {
+        let mut context = RuneheartContext::new().unwrap();
+        context
+            .set_active_script(SourceKind::Content(
+                "pub fn tick() { panic!(\"rate limited\") }".to_string(),
+            ))
+            .unwrap();
+
+        assert!(matches!(
+            context.callback_tick_test(),
+            Err(RuneVmError(_))
+        ));
+    }
+
+    #[test]
+    fn test_generator_runs_past_budget_slice() {
+        let mut context = RuneheartContext::new().unwrap();
+        context.set_budget(ExecutionBudget {
+            instructions: Some(10_000_000),
+            deadline: None,
+        });
+        // both halves run well over BUDGET_SLICE instructions, so the generator gets halted and
+        // resumed several times between yields
+        context
+            .set_active_script(SourceKind::Content(
+                r#"
+                pub fn tick() {
+                    let n = 0;
+                    for i in 0..20000 { n += 1; }
+                    yield n;
+                    let m = 0;
+                    for i in 0..30000 { m += 1; }
+                    m
+                }
+                "#
+                .to_string(),
+            ))
+            .unwrap();
+
+        let first = context.callback_tick_test().unwrap();
+        assert_eq!(rune::from_value::<i64>(first).unwrap(), 20000);
+        assert_eq!(
+            context.script_state(ACTIVE_SCRIPT_ID),
+            Some(ScriptState::Suspended)
+        );
+
+        let second = context.callback_tick_test().unwrap();
+        assert_eq!(rune::from_value::<i64>(second).unwrap(), 30000);
+        assert_eq!(
+            context.script_state(ACTIVE_SCRIPT_ID),
+            Some(ScriptState::Finished)
+        );
+    }
+
+    #[test]
+    fn test_compile_diagnostics() {
+        let mut context = RuneheartContext::new().unwrap();
+        let result = context.set_active_script(SourceKind::Content(
+            "pub fn tick() {\n    let x = ;\n}".to_string(),
+        ));
+
+        let Err(RuneDiagnosticError(diagnostics)) = result else {
+            panic!("expected a diagnostic error");
+        };
+
+        assert_eq!(diagnostics, context.diagnostics(ACTIVE_SCRIPT_ID));
+        let error = diagnostics
+            .iter()
+            .find(|diagnostic| diagnostic.severity == DiagnosticSeverity::Error)
+            .unwrap();
+        assert_eq!(error.line, 2);
+    }
+
+    #[test]
+    fn test_runtime_error_location() {
+        let mut context = RuneheartContext::new().unwrap();
+        context
+            .set_active_script(SourceKind::Content(
+                "pub fn tick() {\n    let v = [];\n    v[3]\n}".to_string(),
+            ))
+            .unwrap();
+
+        assert!(context.callback_tick_test().is_err());
+
+        let error = context
+            .script(ACTIVE_SCRIPT_ID)
+            .and_then(ActiveScript::last_error)
+            .unwrap();
+        assert_eq!(error.frames.first().map(|frame| frame.line), Some(3));
+    }
+
+    #[test]
+    fn test_quarantine_after_failures() {
+        let mut context = RuneheartContext::new().unwrap();
+        context.set_failure_policy(FailurePolicy {
+            max_consecutive_failures: Some(3),
+            backoff: false,
+            max_backoff_ticks: 0,
+        });
+        context
+            .set_active_script(SourceKind::Content(
+                "pub fn tick() { [][0] }".to_string(),
+            ))
+            .unwrap();
+
+        for _ in 0..3 {
+            assert!(context.callback_tick_test().is_err());
+        }
+
+        assert_eq!(
+            context.script_state(ACTIVE_SCRIPT_ID),
+            Some(ScriptState::Quarantined)
+        );
+        assert!(context.callback_tick_test().is_ok());
+
+        context.reenable_script(ACTIVE_SCRIPT_ID).unwrap();
+        assert!(context.callback_tick_test().is_err());
+    }
+
+    #[test]
+    fn test_library_modules() {
+        let scripts = BTreeMap::from([
+            (
+                "main.rn".to_string(),
+                "mod util;\npub fn tick() { util::value() }".to_string(),
+            ),
+            ("util.rn".to_string(), "pub fn value() { 7 }".to_string()),
+        ]);
+
+        let mut context = RuneheartContext::new().unwrap();
+        context
+            .set_active_script(SourceKind::Library {
+                entry: "main.rn".to_string(),
+                scripts,
+            })
+            .unwrap();
+
+        let value = context.callback_tick_test().unwrap();
+        assert_eq!(rune::from_value::<i64>(value).unwrap(), 7);
+    }
+
+    #[test]
+    fn test_sandboxed_has_no_fs() {
+        let mut context = RuneheartContext::new().unwrap();
+        let result = context.set_active_script(SourceKind::Content(
+            "pub fn tick() { fs::read_to_string(\"/etc/passwd\") }".to_string(),
+        ));
+
+        assert!(matches!(result, Err(RuneDiagnosticError(_))));
+    }
+
+    // an empty directory under the system temp dir for tests that need scripts on disk
+    fn scripts_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("runeheart-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("nested")).unwrap();
+
+        dir
+    }
+
+    #[test]
+    fn test_path_outside_scripts_root_is_rejected() {
+        let dir = scripts_dir("outside-root");
+        let script = dir.join("script.rn");
+        fs::write(&script, "pub fn tick() { 1 }").unwrap();
+
+        let mut context = RuneheartContext::new().unwrap();
+        let result = context.set_active_script(SourceKind::Path(script.clone()));
+        assert!(matches!(result, Err(OutsideScriptsRoot(_))));
+
+        // `..` is resolved before the prefix check
+        context.set_scripts_root(Some(dir.join("nested")));
+        let escaping = dir.join("nested").join("..").join("script.rn");
+        let result = context.set_active_script(SourceKind::Path(escaping));
+        assert!(matches!(result, Err(OutsideScriptsRoot(_))));
+
+        context.set_scripts_root(Some(dir.clone()));
+        context.set_active_script(SourceKind::Path(script)).unwrap();
+        let result = context.callback_tick_test().unwrap();
+        assert_eq!(rune::from_value::<i64>(result).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_path_script_reloads_on_change() {
+        let dir = scripts_dir("reload");
+        let script = dir.join("script.rn");
+        fs::write(&script, "pub fn tick() { 1 }").unwrap();
+
+        let mut context = RuneheartContext::new().unwrap();
+        context.set_scripts_root(Some(dir));
+        context.set_active_script(SourceKind::Path(script.clone())).unwrap();
+        let result = context.callback_tick_test().unwrap();
+        assert_eq!(rune::from_value::<i64>(result).unwrap(), 1);
+
+        fs::write(&script, "pub fn tick() { 2 }").unwrap();
+
+        // watcher events arrive on their own thread, give them a moment
+        let reloaded = (0..100).any(|_| {
+            std::thread::sleep(Duration::from_millis(20));
+            context.reload_changed_scripts();
+            let result = context.callback_tick_test().unwrap();
+            rune::from_value::<i64>(result).unwrap() == 2
+        });
+        assert!(reloaded);
+    }
+
+    #[test]
+    fn test_broken_path_script_recompiles_once_fixed() {
+        let dir = scripts_dir("broken");
+        let script = dir.join("script.rn");
+        fs::write(&script, "pub fn tick() { let x = ; }").unwrap();
+
+        let mut context = RuneheartContext::new().unwrap();
+        context.set_scripts_root(Some(dir));
+        let result = context.set_active_script(SourceKind::Path(script.clone()));
+        assert!(matches!(result, Err(RuneDiagnosticError(_))));
+
+        fs::write(&script, "pub fn tick() { 2 }").unwrap();
+
+        let reloaded = (0..100).any(|_| {
+            std::thread::sleep(Duration::from_millis(20));
+            context.reload_changed_scripts();
+            context.script_state(ACTIVE_SCRIPT_ID).is_some()
+        });
+        assert!(reloaded);
+        let result = context.callback_tick_test().unwrap();
+        assert_eq!(rune::from_value::<i64>(result).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_broken_path_script_is_not_recompiled_on_every_add() {
+        let dir = scripts_dir("broken-readd");
+        let script = dir.join("script.rn");
+        fs::write(&script, "pub fn tick() { let x = ; }").unwrap();
+
+        let mut context = RuneheartContext::new().unwrap();
+        context.set_scripts_root(Some(dir));
+        let result = context.set_active_script(SourceKind::Path(script.clone()));
+        assert!(matches!(result, Err(RuneDiagnosticError(_))));
+
+        // the file is fixed but nothing reloaded it yet, adding it again replays the old
+        // failure instead of reading and compiling it again
+        fs::write(&script, "pub fn tick() { 2 }").unwrap();
+        let result = context.set_active_script(SourceKind::Path(script));
+        assert!(matches!(result, Err(RuneDiagnosticError(_))));
+        assert!(context.script_state(ACTIVE_SCRIPT_ID).is_none());
+    }
+
+    #[test]
+    fn test_console_capture() {
+        let mut context = RuneheartContext::new().unwrap();
+        context
+            .set_active_script(SourceKind::Content(
+                "pub fn tick() { println!(\"hello\"); println!(\"world\"); }".to_string(),
+            ))
+            .unwrap();
+
+        context.callback_tick_test().unwrap();
+
+        let lines = context.drain_console();
+        let text: Vec<_> = lines.iter().map(|line| line.text.as_str()).collect();
+        assert_eq!(text, ["hello", "world"]);
+        assert!(lines.iter().all(|line| line.script == ACTIVE_SCRIPT_ID && line.tick == 1));
+        assert!(context.drain_console().is_empty());
+    }
+
+    #[test]
+    fn test_unchanged_source_is_not_reloaded() {
+        let source = || SourceKind::Content("pub fn tick() { yield 1; 2 }".to_string());
+
+        let mut context = RuneheartContext::new().unwrap();
+        context.set_active_script(source()).unwrap();
+        context.callback_tick_test().unwrap();
+
+        // re-adding the same source must not throw away the suspended generator
+        context.set_active_script(source()).unwrap();
+        assert_eq!(
+            context.script_state(ACTIVE_SCRIPT_ID),
+            Some(ScriptState::Suspended)
+        );
+        assert!(context.unloading.is_empty());
+    }
+
+    #[test]
+    fn test_state_roundtrip() {
+        let mut context = RuneheartContext::new().unwrap();
+        for (id, counter) in [("a", 42i64), ("b", 7)] {
+            let mut object = Object::new();
+            object
+                .insert(
+                    rune::alloc::String::try_from("counter").unwrap(),
+                    rune::to_value(counter).unwrap(),
+                )
+                .unwrap();
+            context
+                .states
+                .insert(id.to_string(), rune::to_value(object).unwrap());
+        }
+        let saved = context.save_state().unwrap();
+
+        let mut restored = RuneheartContext::new().unwrap();
+        restored.load_state(&saved).unwrap();
+
+        for (id, counter) in [("a", 42i64), ("b", 7)] {
+            let object = rune::from_value::<Object>(restored.state(id).unwrap().clone()).unwrap();
+            let value = object.get("counter").cloned().unwrap();
+            assert_eq!(rune::from_value::<i64>(value).unwrap(), counter);
+        }
+    }
+
+    #[test]
+    fn test_loaded_state_survives_ticks_before_scripts_are_added() {
+        let mut context = RuneheartContext::new().unwrap();
+        context
+            .add_script(
+                "a",
+                SourceKind::Content(
+                    "pub fn on_load(ctx, state) { state[\"loaded\"] = true; } pub fn tick() {}"
+                        .to_string(),
+                ),
+            )
+            .unwrap();
+        context.callback_tick_all_test();
+        let saved = context.save_state().unwrap();
+
+        // a chunk reload: the state comes back before any script does
+        let mut restored = RuneheartContext::new().unwrap();
+        restored.load_state(&saved).unwrap();
+        assert!(restored.callback_tick_all_test().is_empty());
+        assert!(restored.state("a").is_some());
+        assert!(!restored.take_state_changed().unwrap());
+        assert_eq!(restored.save_state().unwrap(), saved);
+    }
+
+    #[test]
+    fn test_removed_script_drops_its_state() {
+        let mut context = RuneheartContext::new().unwrap();
+        for id in ["a", "b"] {
+            context
+                .add_script(id, SourceKind::Content("pub fn tick() {}".to_string()))
+                .unwrap();
+        }
+        context.callback_tick_all_test();
+
+        // a replacement keeps its state, a removal doesn't
+        context
+            .add_script("a", SourceKind::Content("pub fn tick() { 1 }".to_string()))
+            .unwrap();
+        assert!(context.remove_script("b"));
+        context.callback_tick_all_test();
+
+        assert!(context.state("a").is_some());
+        assert!(context.state("b").is_none());
+    }
+
+    #[test]
+    fn test_scripts_have_separate_state() {
+        let mut context = RuneheartContext::new().unwrap();
+        for id in ["a", "b"] {
+            context
+                .add_script(
+                    id,
+                    SourceKind::Content(format!(
+                        "pub fn on_load(ctx, state) {{ state[\"name\"] = \"{id}\"; }} pub fn tick() {{}}"
+                    )),
+                )
+                .unwrap();
+        }
+
+        context.callback_tick_all_test();
+
+        for id in ["a", "b"] {
+            let state = rune::from_value::<Object>(context.state(id).unwrap().clone()).unwrap();
+            let name = state.get("name").cloned().unwrap();
+            assert_eq!(rune::from_value::<String>(name).unwrap(), id);
+        }
+    }
+
+    // ids of the scripts that ran this tick, in id order
+    fn ticked_ids(context: &mut RuneheartContext) -> Vec<String> {
+        let mut ids = context
+            .callback_tick_all_test()
+            .into_iter()
+            .map(|(id, result)| {
+                assert!(result.is_ok());
+                id
+            })
+            .collect::<Vec<_>>();
+        ids.sort();
+
+        ids
+    }
+
+    #[test]
+    fn test_add_remove_and_disable_scripts() {
+        let mut context = RuneheartContext::new().unwrap();
+        context
+            .add_script("a", SourceKind::Content("pub fn tick() { 1 }".to_string()))
+            .unwrap();
+        context
+            .add_script("b", SourceKind::Content("pub fn tick() { 2 }".to_string()))
+            .unwrap();
+        assert_eq!(ticked_ids(&mut context), ["a", "b"]);
+
+        context.set_script_enabled("a", false).unwrap();
+        assert_eq!(ticked_ids(&mut context), ["b"]);
+
+        context.set_script_enabled("a", true).unwrap();
+        assert!(context.remove_script("b"));
+        assert!(!context.remove_script("b"));
+        assert_eq!(ticked_ids(&mut context), ["a"]);
+        assert!(matches!(context.set_script_enabled("b", true), Err(NoSuchScript(_))));
+    }
+
+    #[test]
+    fn test_scripts_resume_independently() {
+        let mut context = RuneheartContext::new().unwrap();
+        context
+            .add_script(
+                "a",
+                SourceKind::Content("pub fn tick() { yield 1; yield 2; 3 }".to_string()),
+            )
+            .unwrap();
+        context
+            .add_script("b", SourceKind::Content("pub fn tick() { 10 }".to_string()))
+            .unwrap();
+
+        for expected in [1i64, 2, 3] {
+            let results = context
+                .callback_tick_all_test()
+                .into_iter()
+                .map(|(id, result)| (id, rune::from_value::<i64>(result.unwrap()).unwrap()))
+                .collect::<BTreeMap<_, _>>();
+
+            assert_eq!(results.get("a"), Some(&expected));
+            assert_eq!(results.get("b"), Some(&10));
+        }
+    }
+
+    #[test]
+    fn test_on_load_receives_state() {
+        let mut context = RuneheartContext::new().unwrap();
+        context
+            .set_active_script(SourceKind::Content(
+                "pub fn on_load(ctx, state) { state[\"loaded\"] = true; } pub fn tick() {}"
+                    .to_string(),
+            ))
+            .unwrap();
+
+        context.callback_tick_test().unwrap();
+
+        let state = rune::from_value::<Object>(context.state(ACTIVE_SCRIPT_ID).unwrap().clone())
+            .unwrap();
+        let loaded = state.get("loaded").cloned().unwrap();
+        assert!(rune::from_value::<bool>(loaded).unwrap());
+    }
+
+    #[test]
+    fn test_state_changed_only_on_write() {
+        let mut context = RuneheartContext::new().unwrap();
+        assert!(!context.take_state_changed().unwrap());
+
+        context
+            .set_active_script(SourceKind::Content(
+                "pub fn on_load(ctx, state) { state[\"loaded\"] = true; } pub fn tick() {}"
+                    .to_string(),
+            ))
+            .unwrap();
+
+        context.callback_tick_test().unwrap();
+        assert!(context.take_state_changed().unwrap());
+
+        context.callback_tick_test().unwrap();
+        assert!(!context.take_state_changed().unwrap());
+
+        let saved = context.save_state().unwrap();
+        let mut restored = RuneheartContext::new().unwrap();
+        restored.load_state(&saved).unwrap();
+        assert!(!restored.take_state_changed().unwrap());
+    }
+
+    #[test]
+    fn test_yield_resumes_next_tick() {
+        let mut context = RuneheartContext::new().unwrap();
+        context
+            .set_active_script(SourceKind::Content(
+                "pub fn tick() { yield 1; yield 2; 3 }".to_string(),
+            ))
+            .unwrap();
+
+        for expected in [1, 2] {
+            let value = context.callback_tick_test().unwrap();
+            assert_eq!(rune::from_value::<i64>(value).unwrap(), expected);
+            assert_eq!(
+                context.script_state(ACTIVE_SCRIPT_ID),
+                Some(ScriptState::Suspended)
+            );
+        }
+
+        let value = context.callback_tick_test().unwrap();
+        assert_eq!(rune::from_value::<i64>(value).unwrap(), 3);
+        assert_eq!(
+            context.script_state(ACTIVE_SCRIPT_ID),
+            Some(ScriptState::Finished)
+        );
+    }
+}
@@ -0,0 +1,181 @@
+use crate::script::context::RuneheartExecutionError;
+use rune::ast::{Span, Spanned};
+use rune::diagnostics::{Diagnostic, FatalDiagnosticKind};
+use rune::{Diagnostics, SourceId, Sources};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticSeverity {
+    Error,
+    Warning,
+}
+
+/// a single compiler diagnostic in a shape the block screen can draw.
+/// `line`/`column` are 1-based, `span` is a byte range into the source.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScriptDiagnostic {
+    pub severity: DiagnosticSeverity,
+    pub message: String,
+    pub source_name: String,
+    pub span: (usize, usize),
+    pub line: usize,
+    pub column: usize,
+    pub notes: Vec<String>,
+}
+
+/// a resolved position in a script, `line`/`column` are 1-based
+struct Location {
+    source_name: String,
+    span: (usize, usize),
+    line: usize,
+    column: usize,
+}
+
+fn locate(sources: &Sources, source_id: SourceId, span: Option<Span>) -> Location {
+    let source = sources.get(source_id);
+    let span = span.map_or((0, 0), |span| {
+        (span.start.into_usize(), span.end.into_usize())
+    });
+    let (line, column) = source
+        .map(|source| source.pos_to_utf8_linecol(span.0))
+        .unwrap_or_default();
+
+    Location {
+        source_name: source
+            .map(|source| source.name().to_string())
+            .unwrap_or_default(),
+        span,
+        line: line + 1,
+        column: column + 1,
+    }
+}
+
+fn notes_of(error: &dyn Error) -> Vec<String> {
+    let mut notes = Vec::new();
+    let mut source = error.source();
+
+    while let Some(error) = source {
+        notes.push(error.to_string());
+        source = error.source();
+    }
+
+    notes
+}
+
+pub fn collect(diagnostics: &Diagnostics, sources: &Sources) -> Vec<ScriptDiagnostic> {
+    diagnostics
+        .diagnostics()
+        .iter()
+        .filter_map(|diagnostic| {
+            let (severity, source_id, span, message, notes) = match diagnostic {
+                Diagnostic::Fatal(fatal) => {
+                    let (span, notes) = match fatal.kind() {
+                        FatalDiagnosticKind::CompileError(error) => {
+                            (Some(error.span()), notes_of(error))
+                        }
+                        _ => (None, Vec::new()),
+                    };
+
+                    (
+                        DiagnosticSeverity::Error,
+                        fatal.source_id(),
+                        span,
+                        fatal.to_string(),
+                        notes,
+                    )
+                }
+                Diagnostic::Warning(warning) => (
+                    DiagnosticSeverity::Warning,
+                    warning.source_id(),
+                    Some(warning.span()),
+                    warning.to_string(),
+                    Vec::new(),
+                ),
+                _ => return None,
+            };
+
+            let location = locate(sources, source_id, span);
+
+            Some(ScriptDiagnostic {
+                severity,
+                message,
+                source_name: location.source_name,
+                span: location.span,
+                line: location.line,
+                column: location.column,
+                notes,
+            })
+        })
+        .collect()
+}
+
+/// one frame of a script's call stack at the point it failed, innermost first
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StackFrame {
+    pub function: Option<String>,
+    pub source_name: String,
+    pub span: (usize, usize),
+    pub line: usize,
+    pub column: usize,
+}
+
+/// a runtime failure of a script, what the block screen shows when a script stops
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScriptError {
+    pub message: String,
+    pub frames: Vec<StackFrame>,
+}
+
+impl ScriptError {
+    pub fn new(err: &RuneheartExecutionError, sources: &Sources) -> Self {
+        let RuneheartExecutionError::RuneVmError(vm_error) = err else {
+            return Self {
+                message: format!("{:?}", err),
+                frames: Vec::new(),
+            };
+        };
+
+        let mut frames = Vec::new();
+
+        if let Some(location) = vm_error.first_location()
+            && let Some(debug_info) = location.unit.debug_info()
+        {
+            let ips = [location.ip]
+                .into_iter()
+                .chain(location.frames.iter().rev().map(|frame| frame.ip));
+
+            for ip in ips {
+                let Some(inst) = debug_info.instruction_at(ip) else {
+                    continue;
+                };
+
+                let location = locate(sources, inst.source_id, Some(inst.span));
+                frames.push(StackFrame {
+                    function: debug_info
+                        .function_at(ip)
+                        .map(|(_, signature)| signature.to_string()),
+                    source_name: location.source_name,
+                    span: location.span,
+                    line: location.line,
+                    column: location.column,
+                });
+            }
+        }
+
+        Self {
+            message: vm_error.to_string(),
+            frames,
+        }
+    }
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.frames.first() {
+            Some(frame) => write!(f, "line {}: {}", frame.line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
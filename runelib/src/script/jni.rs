@@ -1,21 +1,42 @@
 use crate::example_block::jni::ExampleBlockRenderData;
-use crate::script::context::{RuneheartContext, RuneheartExecutionError};
-use crate::script::context::RuneheartExecutionError::NoActiveScript;
+use crate::script::capabilities::CapabilityProfile;
+use crate::script::context::{
+    ExecutionBudget, FailurePolicy, RuneheartContext, RuneheartExecutionError, ScriptState,
+    SourceKind,
+};
+use crate::script::context::RuneheartExecutionError::{NoActiveScript, NoSuchScript};
+use crate::script::diagnostics::ScriptError;
+use crate::script::rune_module::bindings;
 use crate::script::rune_module::{JNIBlockContext, ScriptableBlockEntity};
-use ciborium::from_reader;
+use ciborium::{from_reader, into_writer};
 use jni::JNIEnv;
 use jni::objects::{JByteArray, JClass, JObject, JObjectArray, JString};
-use jni::sys::{jlong, jobject};
+use jni::sys::{jboolean, jint, jlong, jobject};
 use rune::alloc::iter::TryFromIterator;
 use rune::runtime::VmError;
+use std::path::PathBuf;
+use std::time::Duration;
 
 #[allow(non_snake_case)]
 #[unsafe(no_mangle)]
 pub extern "system" fn Java_rose_runeheart_Native_createContext<'local>(
     mut env: JNIEnv<'local>,
     _: JClass<'local>,
+    // ordinal of CapabilityProfile on the kotlin side
+    profile: jint,
 ) -> jlong {
-    let context = RuneheartContext::new();
+    let profile = match profile {
+        1 => CapabilityProfile::Trusted,
+        _ => CapabilityProfile::Sandboxed,
+    };
+
+    // resolve the jni bindings scripts use up front, a failure here only matters once a script
+    // moves something and is reported there too
+    if let Err(err) = bindings::get(&mut env) {
+        eprintln!("failed to resolve script jni bindings: {:?}", err);
+    }
+
+    let context = RuneheartContext::with_profile(profile);
     match context {
         Ok(context) => {
             let context = Box::new(context);
@@ -39,33 +60,371 @@ pub extern "system" fn Java_rose_runeheart_Native_deleteContext<'local>(
     unsafe { drop(Box::from_raw(context as usize as *mut RuneheartContext)) };
 }
 
+#[allow(non_snake_case)]
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_rose_runeheart_Native_setExecutionBudget<'local>(
+    _: JNIEnv<'local>,
+    _: JClass<'local>,
+    context: jlong,
+    // <= 0 means unlimited
+    instructions: jlong,
+    deadline_micros: jlong,
+) {
+    let context = RuneheartContext::from_handle_mut(context);
+    context.set_budget(ExecutionBudget {
+        instructions: (instructions > 0).then_some(instructions as usize),
+        deadline: (deadline_micros > 0).then(|| Duration::from_micros(deadline_micros as u64)),
+    });
+}
+
+#[allow(non_snake_case)]
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_rose_runeheart_Native_setScriptsRoot<'local>(
+    mut env: JNIEnv<'local>,
+    _: JClass<'local>,
+    context: jlong,
+    root: JString<'local>,
+) {
+    let Ok(root) = env.get_string(&root).map(String::from) else {
+        return;
+    };
+
+    let context = RuneheartContext::from_handle_mut(context);
+    context.set_scripts_root(Some(PathBuf::from(root)));
+}
+
+#[allow(non_snake_case)]
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_rose_runeheart_Native_saveScriptState<'local>(
+    mut env: JNIEnv<'local>,
+    _: JClass<'local>,
+    context: jlong,
+) -> JByteArray<'local> {
+    let context = RuneheartContext::from_handle(context);
+    match context.save_state() {
+        Ok(encoded) => env.byte_array_from_slice(&encoded).unwrap_or_default(),
+        Err(err) => {
+            env.throw_new("java/lang/RuntimeException", format!("{:?}", err))
+                .expect("failed to throw runtime exception?");
+            JByteArray::default()
+        }
+    }
+}
+
+#[allow(non_snake_case)]
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_rose_runeheart_Native_takeScriptStateChanged<'local>(
+    mut env: JNIEnv<'local>,
+    _: JClass<'local>,
+    context: jlong,
+) -> jboolean {
+    let context = RuneheartContext::from_handle_mut(context);
+    match context.take_state_changed() {
+        Ok(changed) => changed as jboolean,
+        Err(err) => {
+            env.throw_new("java/lang/RuntimeException", format!("{:?}", err))
+                .expect("failed to throw runtime exception?");
+            false as jboolean
+        }
+    }
+}
+
+#[allow(non_snake_case)]
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_rose_runeheart_Native_loadScriptState<'local>(
+    mut env: JNIEnv<'local>,
+    _: JClass<'local>,
+    context: jlong,
+    state: JByteArray<'local>,
+) {
+    let Ok(bytes) = env.convert_byte_array(state) else {
+        return;
+    };
+
+    let context = RuneheartContext::from_handle_mut(context);
+    if let Err(err) = context.load_state(&bytes) {
+        env.throw_new("java/lang/RuntimeException", format!("{:?}", err))
+            .expect("failed to throw runtime exception?");
+    }
+}
+
+#[allow(non_snake_case)]
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_rose_runeheart_Native_addScript<'local>(
+    mut env: JNIEnv<'local>,
+    _: JClass<'local>,
+    context: jlong,
+    id: JString<'local>,
+    content: JString<'local>,
+) {
+    let (Ok(id), Ok(content)) = (env.get_string(&id), env.get_string(&content)) else {
+        return;
+    };
+    let (id, content): (String, String) = (id.into(), content.into());
+
+    let context = RuneheartContext::from_handle_mut(context);
+    if let Err(err) = context.add_script(id, SourceKind::Content(content)) {
+        env.throw_new("java/lang/RuntimeException", format!("{:?}", err))
+            .expect("failed to throw runtime exception?");
+    }
+}
+
+#[allow(non_snake_case)]
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_rose_runeheart_Native_removeScript<'local>(
+    mut env: JNIEnv<'local>,
+    _: JClass<'local>,
+    context: jlong,
+    id: JString<'local>,
+) -> jboolean {
+    let Ok(id) = env.get_string(&id) else {
+        return 0;
+    };
+
+    let context = RuneheartContext::from_handle_mut(context);
+    context.remove_script(&String::from(id)) as jboolean
+}
+
+#[allow(non_snake_case)]
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_rose_runeheart_Native_setScriptEnabled<'local>(
+    mut env: JNIEnv<'local>,
+    _: JClass<'local>,
+    context: jlong,
+    id: JString<'local>,
+    enabled: jboolean,
+) {
+    let Ok(id) = env.get_string(&id) else {
+        return;
+    };
+
+    let context = RuneheartContext::from_handle_mut(context);
+    if let Err(err) = context.set_script_enabled(&String::from(id), enabled != 0) {
+        env.throw_new("java/lang/RuntimeException", format!("{:?}", err))
+            .expect("failed to throw runtime exception?");
+    }
+}
+
+#[allow(non_snake_case)]
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_rose_runeheart_Native_getScriptDiagnostics<'local>(
+    mut env: JNIEnv<'local>,
+    _: JClass<'local>,
+    context: jlong,
+    id: JString<'local>,
+) -> JByteArray<'local> {
+    let Ok(id) = env.get_string(&id).map(String::from) else {
+        return JByteArray::default();
+    };
+
+    let context = RuneheartContext::from_handle(context);
+    let mut encoded = Vec::new();
+    match into_writer(context.diagnostics(&id), &mut encoded) {
+        Ok(_) => env.byte_array_from_slice(&encoded).unwrap_or_default(),
+        Err(err) => {
+            env.throw_new("java/lang/RuntimeException", format!("{:?}", err))
+                .expect("failed to throw runtime exception?");
+            JByteArray::default()
+        }
+    }
+}
+
+#[allow(non_snake_case)]
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_rose_runeheart_Native_getLastScriptError<'local>(
+    mut env: JNIEnv<'local>,
+    _: JClass<'local>,
+    context: jlong,
+    id: JString<'local>,
+) -> JByteArray<'local> {
+    let Ok(id) = env.get_string(&id).map(String::from) else {
+        return JByteArray::default();
+    };
+
+    let context = RuneheartContext::from_handle(context);
+    let error = context.script(&id).and_then(|script| script.last_error());
+    encode_script_error(&mut env, error)
+}
+
+#[allow(non_snake_case)]
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_rose_runeheart_Native_getQuarantineReason<'local>(
+    mut env: JNIEnv<'local>,
+    _: JClass<'local>,
+    context: jlong,
+    id: JString<'local>,
+) -> JByteArray<'local> {
+    let Ok(id) = env.get_string(&id).map(String::from) else {
+        return JByteArray::default();
+    };
+
+    let context = RuneheartContext::from_handle(context);
+    let error = context.script(&id).and_then(|script| script.quarantine_reason());
+    encode_script_error(&mut env, error)
+}
+
+fn encode_script_error<'local>(
+    env: &mut JNIEnv<'local>,
+    error: Option<&ScriptError>,
+) -> JByteArray<'local> {
+    let Some(error) = error else {
+        return JByteArray::default();
+    };
+
+    let mut encoded = Vec::new();
+    match into_writer(error, &mut encoded) {
+        Ok(_) => env.byte_array_from_slice(&encoded).unwrap_or_default(),
+        Err(err) => {
+            env.throw_new("java/lang/RuntimeException", format!("{:?}", err))
+                .expect("failed to throw runtime exception?");
+            JByteArray::default()
+        }
+    }
+}
+
+#[allow(non_snake_case)]
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_rose_runeheart_Native_drainConsole<'local>(
+    mut env: JNIEnv<'local>,
+    _: JClass<'local>,
+    context: jlong,
+) -> JByteArray<'local> {
+    let context = RuneheartContext::from_handle_mut(context);
+    let mut encoded = Vec::new();
+    match into_writer(&context.drain_console(), &mut encoded) {
+        Ok(_) => env.byte_array_from_slice(&encoded).unwrap_or_default(),
+        Err(err) => {
+            env.throw_new("java/lang/RuntimeException", format!("{:?}", err))
+                .expect("failed to throw runtime exception?");
+            JByteArray::default()
+        }
+    }
+}
+
+#[allow(non_snake_case)]
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_rose_runeheart_Native_getScriptState<'local>(
+    mut env: JNIEnv<'local>,
+    _: JClass<'local>,
+    context: jlong,
+    id: JString<'local>,
+) -> jint {
+    let Ok(id) = env.get_string(&id) else {
+        return -1;
+    };
+
+    // matches the ordinals of ScriptState on the kotlin side, -1 when there is no such script
+    match RuneheartContext::from_handle(context).script_state(&String::from(id)) {
+        None => -1,
+        Some(ScriptState::Running) => 0,
+        Some(ScriptState::Suspended) => 1,
+        Some(ScriptState::Finished) => 2,
+        Some(ScriptState::Quarantined) => 3,
+    }
+}
+
+#[allow(non_snake_case)]
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_rose_runeheart_Native_reenableScript<'local>(
+    mut env: JNIEnv<'local>,
+    _: JClass<'local>,
+    context: jlong,
+    id: JString<'local>,
+) {
+    let Ok(id) = env.get_string(&id) else {
+        return;
+    };
+
+    let context = RuneheartContext::from_handle_mut(context);
+    if let Err(err) = context.reenable_script(&String::from(id)) {
+        env.throw_new("java/lang/RuntimeException", format!("{:?}", err))
+            .expect("failed to throw runtime exception?");
+    }
+}
+
+#[allow(non_snake_case)]
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_rose_runeheart_Native_setFailurePolicy<'local>(
+    _: JNIEnv<'local>,
+    _: JClass<'local>,
+    context: jlong,
+    // <= 0 means never quarantine
+    max_consecutive_failures: jint,
+    backoff: jboolean,
+    max_backoff_ticks: jint,
+) {
+    let context = RuneheartContext::from_handle_mut(context);
+    context.set_failure_policy(FailurePolicy {
+        max_consecutive_failures: (max_consecutive_failures > 0)
+            .then_some(max_consecutive_failures as u32),
+        backoff: backoff != 0,
+        max_backoff_ticks: max_backoff_ticks.max(0) as u32,
+    });
+}
+
 #[allow(non_snake_case)]
 #[unsafe(no_mangle)]
 pub extern "system" fn Java_rose_runeheart_Native_tick<'local>(
+    env: JNIEnv<'local>,
+    _: JClass<'local>,
+    context: jlong,
+    object: JObject<'local>,
+    raw: JObjectArray,
+    scriptable: JByteArray<'local>,
+) {
+    if let Ok(bytes) = env.convert_byte_array(scriptable)
+        && let Ok(scriptables) = from_reader::<Vec<ScriptableBlockEntity>, _>(&bytes[..])
+    {
+        let context = RuneheartContext::from_handle_mut(context);
+        let transfers = context.transfers().clone();
+        let jni_context = JNIBlockContext::new(&env, &object, &raw, transfers);
+        let scriptables = jni_context.claim(scriptables);
+        // failures are kept on each script, kotlin reads them with getLastScriptError
+        context.callback_tick(jni_context, scriptables);
+    }
+}
+
+#[allow(non_snake_case)]
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_rose_runeheart_Native_unloadScripts<'local>(
+    env: JNIEnv<'local>,
+    _: JClass<'local>,
+    context: jlong,
+    object: JObject<'local>,
+    raw: JObjectArray,
+) {
+    let context = RuneheartContext::from_handle_mut(context);
+    let transfers = context.transfers().clone();
+    let jni_context = JNIBlockContext::new(&env, &object, &raw, transfers);
+    context.unload_scripts(jni_context);
+}
+
+#[allow(non_snake_case)]
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_rose_runeheart_Native_tickScript<'local>(
     mut env: JNIEnv<'local>,
     _: JClass<'local>,
     context: jlong,
+    id: JString<'local>,
     object: JObject<'local>,
     raw: JObjectArray,
     scriptable: JByteArray<'local>,
 ) {
-    // println!(
-    //     "{:?}",
-    //     from_reader::<Vec<ScriptableBlockEntity>, _>(
-    //         &()[..]
-    //     )
-    // );
+    let Ok(id) = env.get_string(&id).map(String::from) else {
+        return;
+    };
 
     if let Ok(bytes) = env.convert_byte_array(scriptable)
         && let Ok(scriptables) = from_reader::<Vec<ScriptableBlockEntity>, _>(&bytes[..])
     {
         let context = RuneheartContext::from_handle_mut(context);
-        if let Err(err) =
-            context.callback_tick(JNIBlockContext::new(&env, &object, &raw), scriptables)
-        {
-            println!("{:?}", err);
-            // env.throw_new("java/lang/RuntimeException", format!("{:?}", err))
-            //     .expect("failed to throw runtime exception?");
+        let transfers = context.transfers().clone();
+        let jni_context = JNIBlockContext::new(&env, &object, &raw, transfers);
+        let scriptables = jni_context.claim(scriptables);
+        // script failures are kept on the script, only a bad id is the caller's problem
+        if let Err(err @ NoSuchScript(_)) = context.tick_script(&id, jni_context, scriptables) {
+            env.throw_new("java/lang/RuntimeException", format!("{:?}", err))
+                .expect("failed to throw runtime exception?");
         }
     }
 }
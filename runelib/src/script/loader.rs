@@ -0,0 +1,44 @@
+use rune::ast::Spanned;
+use rune::compile::{self, ComponentRef, Item, SourceLoader};
+use rune::Source;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// resolves `mod foo;` against an in-memory set of scripts keyed by file name, so `mod foo;`
+/// loads `foo.rn` and `mod foo { mod bar; }` loads `foo/bar.rn`.
+pub struct ScriptSourceLoader {
+    scripts: BTreeMap<String, String>,
+}
+
+impl ScriptSourceLoader {
+    pub fn new(scripts: BTreeMap<String, String>) -> Self {
+        Self { scripts }
+    }
+}
+
+fn module_file_name(item: &Item) -> String {
+    let components: Vec<_> = item
+        .iter()
+        .filter_map(|component| match component {
+            ComponentRef::Str(name) => Some(name),
+            _ => None,
+        })
+        .collect();
+
+    format!("{}.rn", components.join("/"))
+}
+
+impl SourceLoader for ScriptSourceLoader {
+    fn load(&mut self, _: &Path, item: &Item, span: &dyn Spanned) -> compile::Result<Source> {
+        let name = module_file_name(item);
+
+        let Some(content) = self.scripts.get(&name) else {
+            return Err(compile::Error::msg(
+                span,
+                format!("no script named `{}` in the script directory", name),
+            ));
+        };
+
+        Source::new(&name, content).map_err(|err| compile::Error::msg(span, err))
+    }
+}
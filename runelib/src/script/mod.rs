@@ -1,3 +1,9 @@
 mod jni;
+pub mod cache;
+pub mod capabilities;
+pub mod console;
 pub mod context;
-pub mod rune_module;
\ No newline at end of file
+pub mod diagnostics;
+pub mod loader;
+pub mod rune_module;
+pub mod watcher;
\ No newline at end of file
@@ -0,0 +1,110 @@
+use jni::JNIEnv;
+use jni::errors::Result;
+use jni::objects::{GlobalRef, JClass, JMethodID, JStaticMethodID};
+use std::sync::OnceLock;
+
+const BLOCK_ENTITY_CLASS: &str = "rose/runeheart/blockentity/ExampleBlockEntity";
+const DIRECTION_CLASS: &str = "net/minecraft/core/Direction";
+
+const MOVE_ITEM_SIG: &str = "(Lrose/runeheart/blockentity/RawScriptableBlockEntity;Lrose/runeheart/blockentity/RawScriptableBlockEntity;ILnet/minecraft/core/Direction;Ljava/lang/Integer;Z)I";
+const MOVE_FLUID_SIG: &str = "(Lrose/runeheart/blockentity/RawScriptableBlockEntity;Lrose/runeheart/blockentity/RawScriptableBlockEntity;ILnet/minecraft/core/Direction;Ljava/lang/Integer;)I";
+const MOVE_ENERGY_SIG: &str = "(Lrose/runeheart/blockentity/RawScriptableBlockEntity;Lrose/runeheart/blockentity/RawScriptableBlockEntity;Lnet/minecraft/core/Direction;Ljava/lang/Integer;)I";
+const MOVE_ITEMS_SIG: &str = "([B)[I";
+const RAW_SCRIPTABLE_ENTITY_SIG: &str = "(I)Lrose/runeheart/blockentity/RawScriptableBlockEntity;";
+const QUERY_BLOCK_ENTITIES_SIG: &str = "(ILjava/lang/String;ZI)[B";
+
+static BINDINGS: OnceLock<JniBindings> = OnceLock::new();
+
+/// classes, constants and method ids the script api calls into, resolved once per process so
+/// the transfer hot path doesn't look anything up by name
+pub struct JniBindings {
+    /// the `Direction` enum constants in ordinal order
+    pub directions: [GlobalRef; 6],
+    pub integer_class: GlobalRef,
+    // method ids are only valid while their class is loaded, holding the class keeps it that way
+    #[allow(dead_code)]
+    block_entity_class: GlobalRef,
+    pub integer_value_of: JStaticMethodID,
+    pub move_item: JMethodID,
+    pub move_fluid: JMethodID,
+    pub move_energy: JMethodID,
+    pub move_items: JMethodID,
+    pub raw_scriptable_entity: JMethodID,
+    pub query_block_entities: JMethodID,
+}
+
+impl JniBindings {
+    fn resolve(env: &mut JNIEnv) -> Result<Self> {
+        let direction = env.find_class(DIRECTION_CLASS)?;
+        let mut constant = |name: &str| -> Result<GlobalRef> {
+            let value = env
+                .get_static_field(&direction, name, "Lnet/minecraft/core/Direction;")?
+                .l()?;
+            env.new_global_ref(value)
+        };
+        let directions = [
+            constant("DOWN")?,
+            constant("UP")?,
+            constant("NORTH")?,
+            constant("SOUTH")?,
+            constant("WEST")?,
+            constant("EAST")?,
+        ];
+
+        let integer = env.find_class("java/lang/Integer")?;
+        let integer_value_of =
+            env.get_static_method_id(&integer, "valueOf", "(I)Ljava/lang/Integer;")?;
+        let integer_class = env.new_global_ref(integer)?;
+
+        let block_entity = env.find_class(BLOCK_ENTITY_CLASS)?;
+        let block_entity_class = env.new_global_ref(&block_entity)?;
+
+        Ok(Self {
+            directions,
+            integer_class,
+            block_entity_class,
+            integer_value_of,
+            move_item: env.get_method_id(&block_entity, "moveItem", MOVE_ITEM_SIG)?,
+            move_fluid: env.get_method_id(&block_entity, "moveFluid", MOVE_FLUID_SIG)?,
+            move_energy: env.get_method_id(&block_entity, "moveEnergy", MOVE_ENERGY_SIG)?,
+            move_items: env.get_method_id(&block_entity, "moveItems", MOVE_ITEMS_SIG)?,
+            raw_scriptable_entity: env.get_method_id(
+                &block_entity,
+                "rawScriptableEntity",
+                RAW_SCRIPTABLE_ENTITY_SIG,
+            )?,
+            query_block_entities: env.get_method_id(
+                &block_entity,
+                "queryBlockEntities",
+                QUERY_BLOCK_ENTITIES_SIG,
+            )?,
+        })
+    }
+
+    pub fn integer_class(&self) -> &JClass<'static> {
+        <&JClass>::from(self.integer_class.as_obj())
+    }
+}
+
+/// the process wide bindings, resolved with `env` the first time this is called. called on
+/// context creation so the first script tick doesn't pay for it
+pub fn get(env: &mut JNIEnv) -> Result<&'static JniBindings> {
+    if let Some(bindings) = BINDINGS.get() {
+        return Ok(bindings);
+    }
+
+    let bindings = match JniBindings::resolve(env) {
+        Ok(bindings) => bindings,
+        Err(err) => {
+            // a missing class or method leaves an exception pending, don't let it leak into
+            // whatever jni call comes next
+            if env.exception_check().unwrap_or(false) {
+                let _ = env.exception_clear();
+            }
+
+            return Err(err);
+        }
+    };
+
+    Ok(BINDINGS.get_or_init(|| bindings))
+}
@@ -0,0 +1,44 @@
+use crate::script::rune_module::Error;
+use rune::Value;
+use rune::runtime::{Bytes, VmResult};
+use rune::vm_try;
+
+// conversions between script values and cbor/json. anything holding functions, generators or
+// native types that don't implement serde fails with an Error instead of panicking
+
+/// encodes `value` as cbor, the same encoding `ctx.state` is saved with
+#[rune::function]
+fn to_cbor(value: Value) -> VmResult<Result<Bytes, Error>> {
+    let mut encoded = Vec::new();
+    if let Err(err) = ciborium::into_writer(&value, &mut encoded) {
+        return VmResult::Ok(Err(Error::new(format!("cannot encode value as cbor: {}", err))));
+    }
+
+    VmResult::Ok(Ok(vm_try!(Bytes::from_slice(encoded))))
+}
+
+#[rune::function]
+fn from_cbor(bytes: &Bytes) -> Result<Value, Error> {
+    ciborium::from_reader::<Value, _>(bytes.as_slice())
+        .map_err(|err| Error::new(format!("invalid cbor: {}", err)))
+}
+
+#[rune::function]
+fn to_json(value: Value) -> Result<String, Error> {
+    serde_json::to_string(&value)
+        .map_err(|err| Error::new(format!("cannot encode value as json: {}", err)))
+}
+
+#[rune::function]
+fn from_json(json: &str) -> Result<Value, Error> {
+    serde_json::from_str::<Value>(json).map_err(|err| Error::new(format!("invalid json: {}", err)))
+}
+
+pub fn register(m: &mut rune::Module) -> Result<(), rune::ContextError> {
+    m.function_meta(to_cbor)?;
+    m.function_meta(from_cbor)?;
+    m.function_meta(to_json)?;
+    m.function_meta(from_json)?;
+
+    Ok(())
+}
@@ -0,0 +1,188 @@
+use crate::script::rune_module::{Error, ScriptableItem};
+use regex::Regex;
+use rune::alloc::fmt::TryWrite;
+use rune::runtime::{Formatter, VmResult};
+use rune::{Any, vm_write};
+
+/// an item predicate scripts build once and reuse, e.g.
+/// `Filter::tag("minecraft:coals").or(Filter::id("minecraft:charcoal"))`
+#[derive(Any)]
+#[rune(item = ::rune)]
+#[derive(Clone, Debug)]
+pub struct Filter {
+    kind: FilterKind,
+}
+
+#[derive(Clone, Debug)]
+enum FilterKind {
+    Everything,
+    Id(String),
+    Tag(String),
+    Glob(String),
+    Regex(Regex),
+    Count { min: Option<i32>, max: Option<i32> },
+    And(Box<FilterKind>, Box<FilterKind>),
+    Or(Box<FilterKind>, Box<FilterKind>),
+    Not(Box<FilterKind>),
+}
+
+impl FilterKind {
+    fn matches(&self, item: &ScriptableItem) -> bool {
+        match self {
+            FilterKind::Everything => true,
+            FilterKind::Id(id) => item.name == *id,
+            FilterKind::Tag(tag) => item.tags.iter().any(|item_tag| item_tag == tag),
+            FilterKind::Glob(pattern) => glob_matches(pattern, &item.name),
+            FilterKind::Regex(regex) => regex.is_match(&item.name),
+            FilterKind::Count { min, max } => {
+                min.is_none_or(|min| item.count >= min) && max.is_none_or(|max| item.count <= max)
+            }
+            FilterKind::And(a, b) => a.matches(item) && b.matches(item),
+            FilterKind::Or(a, b) => a.matches(item) || b.matches(item),
+            FilterKind::Not(a) => !a.matches(item),
+        }
+    }
+}
+
+// `*` matches any run of characters and `?` exactly one, everything else is literal
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+
+    let (mut p, mut t) = (0, 0);
+    // position of the last `*` and the text position it was tried at
+    let mut star = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                // let the last `*` swallow one more character and retry
+                Some((star_p, star_t)) => {
+                    star = Some((star_p, star_t + 1));
+                    p = star_p + 1;
+                    t = star_t + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+impl Filter {
+    fn new(kind: FilterKind) -> Self {
+        Self { kind }
+    }
+
+    pub fn test(&self, item: &ScriptableItem) -> bool {
+        self.kind.matches(item)
+    }
+
+    #[rune::function(path = Self::everything)]
+    fn everything() -> Filter {
+        Filter::new(FilterKind::Everything)
+    }
+
+    /// exact item id, e.g. `minecraft:iron_ingot`
+    #[rune::function(path = Self::id)]
+    fn id(id: &str) -> Filter {
+        Filter::new(FilterKind::Id(id.to_string()))
+    }
+
+    /// item tag, with or without the leading `#`
+    #[rune::function(path = Self::tag)]
+    fn tag(tag: &str) -> Filter {
+        Filter::new(FilterKind::Tag(tag.trim_start_matches('#').to_string()))
+    }
+
+    /// glob over the item id, `minecraft:*_ore`
+    #[rune::function(path = Self::glob)]
+    fn glob(pattern: &str) -> Filter {
+        Filter::new(FilterKind::Glob(pattern.to_string()))
+    }
+
+    /// regex over the item id, fails if the pattern doesn't compile
+    #[rune::function(path = Self::regex)]
+    fn regex(pattern: &str) -> Result<Filter, Error> {
+        Regex::new(pattern)
+            .map(|regex| Filter::new(FilterKind::Regex(regex)))
+            .map_err(|err| Error::new(err.to_string()))
+    }
+
+    /// stack size between `min` and `max` inclusive, None leaves that side open
+    #[rune::function(path = Self::count)]
+    fn count(min: Option<i32>, max: Option<i32>) -> Filter {
+        Filter::new(FilterKind::Count { min, max })
+    }
+
+    #[rune::function]
+    fn and(&self, other: &Filter) -> Filter {
+        Filter::new(FilterKind::And(
+            Box::new(self.kind.clone()),
+            Box::new(other.kind.clone()),
+        ))
+    }
+
+    #[rune::function]
+    fn or(&self, other: &Filter) -> Filter {
+        Filter::new(FilterKind::Or(
+            Box::new(self.kind.clone()),
+            Box::new(other.kind.clone()),
+        ))
+    }
+
+    #[rune::function]
+    fn not(&self) -> Filter {
+        Filter::new(FilterKind::Not(Box::new(self.kind.clone())))
+    }
+
+    #[rune::function]
+    fn matches(&self, item: &ScriptableItem) -> bool {
+        self.test(item)
+    }
+
+    #[rune::function(protocol = DEBUG_FMT)]
+    pub fn debug_fmt(&self, f: &mut Formatter) -> VmResult<()> {
+        vm_write!(f, "{:?}", self.kind)
+    }
+}
+
+pub fn register(m: &mut rune::Module) -> Result<(), rune::ContextError> {
+    m.ty::<Filter>()?;
+    m.function_meta(Filter::everything)?;
+    m.function_meta(Filter::id)?;
+    m.function_meta(Filter::tag)?;
+    m.function_meta(Filter::glob)?;
+    m.function_meta(Filter::regex)?;
+    m.function_meta(Filter::count)?;
+    m.function_meta(Filter::and)?;
+    m.function_meta(Filter::or)?;
+    m.function_meta(Filter::not)?;
+    m.function_meta(Filter::matches)?;
+    m.function_meta(Filter::debug_fmt)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::glob_matches;
+
+    #[test]
+    fn test_glob() {
+        assert!(glob_matches("minecraft:*_ore", "minecraft:iron_ore"));
+        assert!(glob_matches("*:dirt", "minecraft:dirt"));
+        assert!(glob_matches("minecraft:?irt", "minecraft:dirt"));
+        assert!(!glob_matches("minecraft:*_ore", "minecraft:iron_ingot"));
+        assert!(!glob_matches("minecraft:dirt", "minecraft:dirt_path"));
+    }
+}
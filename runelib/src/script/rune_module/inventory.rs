@@ -0,0 +1,74 @@
+use crate::script::rune_module::filter::Filter;
+use crate::script::rune_module::{ScriptableBlockEntity, ScriptableItem};
+use rune::alloc::clone::TryClone;
+use rune::runtime::VmResult;
+use rune::{Value, vm_try};
+use std::collections::HashMap;
+
+// aggregation across many inventories, the single inventory versions live on ScriptableBlockEntity
+
+/// number of items matching `filter` across every entity
+#[rune::function]
+fn count_of(entities: Vec<Value>, filter: &Filter) -> VmResult<i64> {
+    let mut total = 0;
+
+    for entity in entities {
+        total += vm_try!(entity.borrow_ref::<ScriptableBlockEntity>()).count_matching(filter);
+    }
+
+    VmResult::Ok(total)
+}
+
+/// item id to the number of those items across every entity
+#[rune::function]
+fn totals(entities: Vec<Value>) -> VmResult<HashMap<String, i64>> {
+    let mut totals = HashMap::new();
+
+    for entity in entities {
+        vm_try!(entity.borrow_ref::<ScriptableBlockEntity>()).add_totals(&mut totals);
+    }
+
+    VmResult::Ok(totals)
+}
+
+/// tag to the number of items with that tag across every entity
+#[rune::function]
+fn totals_by_tag(entities: Vec<Value>) -> VmResult<HashMap<String, i64>> {
+    let mut totals = HashMap::new();
+
+    for entity in entities {
+        vm_try!(entity.borrow_ref::<ScriptableBlockEntity>()).add_totals_by_tag(&mut totals);
+    }
+
+    VmResult::Ok(totals)
+}
+
+/// item id to every `(entity, item)` holding it, ready to hand to `move_item`
+#[rune::function]
+fn group_items(
+    entities: Vec<Value>,
+) -> VmResult<HashMap<String, Vec<(Value, ScriptableItem)>>> {
+    let mut groups: HashMap<String, Vec<(Value, ScriptableItem)>> = HashMap::new();
+
+    for entity in entities {
+        let items = vm_try!(vm_try!(entity.borrow_ref::<ScriptableBlockEntity>()).items.try_clone());
+
+        for item in items {
+            groups
+                .entry(item.name.clone())
+                .or_default()
+                .push((entity.clone(), item));
+        }
+    }
+
+    VmResult::Ok(groups)
+}
+
+pub fn register(m: &mut rune::Module) -> Result<(), rune::ContextError> {
+    m.function_meta(count_of)?;
+    m.function_meta(totals)?;
+    m.function_meta(totals_by_tag)?;
+    m.function_meta(group_items)?;
+
+    Ok(())
+}
@@ -1,14 +1,30 @@
 use jni::JNIEnv;
-use jni::objects::{AsJArrayRaw, JByteArray, JObject, JObjectArray, JString, JValue, ReleaseMode};
-use jni::signature::{JavaType, ReturnType};
-use jni::sys::{jint, jobject, jsize};
+use jni::objects::{
+    AsJArrayRaw, JByteArray, JIntArray, JMethodID, JObject, JObjectArray, JString, JValue,
+    ReleaseMode,
+};
+use jni::signature::{JavaType, Primitive, ReturnType};
+use jni::sys::{jboolean, jint, jobject, jsize, jvalue};
 use rune::alloc::clone::TryClone;
 use rune::alloc::fmt::TryWrite;
-use rune::runtime::{Args, Formatter, VmResult};
-use rune::{Any, ContextError, Module, Value, vm_write};
+use rune::runtime::{Args, Formatter, Hasher, VmResult};
+use rune::{Any, ContextError, Module, Value, vm_try, vm_write};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
 use std::ptr::NonNull;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use bindings::JniBindings;
+use filter::Filter;
+use transfer::{QueuedTransfer, TransferQueue};
+
+pub mod bindings;
+pub mod codec;
+pub mod filter;
+pub mod inventory;
+pub mod transfer;
 
 #[rune::module(::rune)]
 
@@ -18,18 +34,72 @@ pub fn module(_stdio: bool) -> Result<Module, ContextError> {
     m.function_meta(Error::display_fmt)?;
     m.function_meta(Error::debug_fmt)?;
     m.ty::<BlockEntityTarget>()?;
+    m.ty::<BlockEntityQuery>()?;
+    m.function_meta(BlockEntityQuery::new)?;
+    m.ty::<TransferError>()?;
+    m.function_meta(TransferError::display_fmt)?;
+    m.function_meta(TransferError::debug_fmt)?;
 
     m.ty::<Direction>()?;
+    m.function_meta(Direction::all)?;
+    m.function_meta(Direction::from_name)?;
+    m.function_meta(Direction::opposite)?;
+    m.function_meta(Direction::rotate_clockwise)?;
+    m.function_meta(Direction::axis)?;
+    m.function_meta(Direction::to_offset)?;
+    m.function_meta(Direction::partial_eq)?;
+    m.function_meta(Direction::display_fmt)?;
+    m.function_meta(Direction::debug_fmt)?;
+    m.ty::<Axis>()?;
+    m.function_meta(Axis::partial_eq)?;
+    m.function_meta(Axis::display_fmt)?;
+    m.function_meta(Axis::debug_fmt)?;
     m.ty::<ScriptableItem>()?;
+    m.ty::<ScriptableEnchantment>()?;
+    m.ty::<ScriptableComponent>()?;
+    m.ty::<ScriptableFluidTank>()?;
+    m.ty::<ScriptableEnergy>()?;
     m.ty::<ScriptableBlockEntity>()?;
     m.ty::<JNIBlockContext>()?;
 
     m.function_meta(JNIBlockContext::move_item)?;
+    m.function_meta(JNIBlockContext::simulate_move)?;
+    m.function_meta(JNIBlockContext::simulate_moves)?;
+    m.function_meta(JNIBlockContext::queue_move)?;
+    m.function_meta(JNIBlockContext::transfer_result)?;
+    m.function_meta(JNIBlockContext::block_entities)?;
+    m.function_meta(JNIBlockContext::move_fluid)?;
+    m.function_meta(JNIBlockContext::move_energy)?;
+
+    m.function_meta(ScriptableItem::durability__meta)?;
+    m.function_meta(ScriptableItem::is_damaged__meta)?;
+    m.function_meta(ScriptableItem::component__meta)?;
 
+    m.function_meta(ScriptableBlockEntity::items_matching)?;
+    m.function_meta(ScriptableBlockEntity::count_of)?;
+    m.function_meta(ScriptableBlockEntity::totals)?;
+    m.function_meta(ScriptableBlockEntity::totals_by_tag)?;
+    m.function_meta(ScriptableBlockEntity::first_slot)?;
+    m.function_meta(ScriptableBlockEntity::free_slots)?;
+    inventory::register(&mut m)?;
+    codec::register(&mut m)?;
     m.function_meta(ScriptableBlockEntity::display_fmt)?;
     m.function_meta(ScriptableBlockEntity::debug_fmt)?;
 
+    filter::register(&mut m)?;
+
     m.ty::<BlockPos>()?;
+    m.function_meta(BlockPos::new__meta)?;
+    m.function_meta(BlockPos::add)?;
+    m.function_meta(BlockPos::sub)?;
+    m.function_meta(BlockPos::offset)?;
+    m.function_meta(BlockPos::manhattan_distance)?;
+    m.function_meta(BlockPos::distance)?;
+    m.function_meta(BlockPos::partial_eq)?;
+    m.function_meta(BlockPos::eq)?;
+    m.function_meta(BlockPos::hash)?;
+    m.function_meta(BlockPos::display_fmt)?;
+    m.function_meta(BlockPos::debug_fmt)?;
 
     #[cfg(test)]
     m.function_meta(tests::get_block_entities_test)?;
@@ -39,7 +109,7 @@ pub fn module(_stdio: bool) -> Result<Module, ContextError> {
 
 #[derive(Any)]
 #[rune(item = ::rune)]
-#[derive(PartialEq, Debug, Deserialize, TryClone)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Deserialize, TryClone)]
 pub struct BlockPos {
     #[rune(get)]
     pub x: i32,
@@ -49,6 +119,87 @@ pub struct BlockPos {
     pub z: i32,
 }
 
+impl BlockPos {
+    #[rune::function(keep, path = Self::new)]
+    fn new(x: i32, y: i32, z: i32) -> Self {
+        Self { x, y, z }
+    }
+
+    // coordinates come straight from scripts, an overflow must not panic inside a jni call so
+    // everything past the i32 range wraps, no real position is anywhere near it
+    #[rune::function(protocol = ADD)]
+    fn add(&self, other: &BlockPos) -> BlockPos {
+        BlockPos::new(
+            self.x.wrapping_add(other.x),
+            self.y.wrapping_add(other.y),
+            self.z.wrapping_add(other.z),
+        )
+    }
+
+    #[rune::function(protocol = SUB)]
+    fn sub(&self, other: &BlockPos) -> BlockPos {
+        BlockPos::new(
+            self.x.wrapping_sub(other.x),
+            self.y.wrapping_sub(other.y),
+            self.z.wrapping_sub(other.z),
+        )
+    }
+
+    /// the position `distance` blocks towards `face`, one block if no distance is given
+    #[rune::function]
+    fn offset(&self, face: Direction, distance: Option<i32>) -> BlockPos {
+        let (x, y, z) = face.delta();
+        let distance = distance.unwrap_or(1);
+
+        BlockPos::new(
+            self.x.wrapping_add(x.wrapping_mul(distance)),
+            self.y.wrapping_add(y.wrapping_mul(distance)),
+            self.z.wrapping_add(z.wrapping_mul(distance)),
+        )
+    }
+
+    #[rune::function]
+    fn manhattan_distance(&self, other: &BlockPos) -> i64 {
+        (self.x as i64 - other.x as i64).abs()
+            + (self.y as i64 - other.y as i64).abs()
+            + (self.z as i64 - other.z as i64).abs()
+    }
+
+    #[rune::function]
+    fn distance(&self, other: &BlockPos) -> f64 {
+        let dx = (self.x as i64 - other.x as i64) as f64;
+        let dy = (self.y as i64 - other.y as i64) as f64;
+        let dz = (self.z as i64 - other.z as i64) as f64;
+
+        (dx * dx + dy * dy + dz * dz).sqrt()
+    }
+
+    #[rune::function(protocol = PARTIAL_EQ)]
+    fn partial_eq(&self, other: &BlockPos) -> bool {
+        self == other
+    }
+
+    #[rune::function(protocol = EQ)]
+    fn eq(&self, other: &BlockPos) -> bool {
+        self == other
+    }
+
+    #[rune::function(protocol = HASH)]
+    fn hash(&self, hasher: &mut Hasher) {
+        Hash::hash(self, hasher);
+    }
+
+    #[rune::function(protocol = DISPLAY_FMT)]
+    pub fn display_fmt(&self, f: &mut Formatter) -> VmResult<()> {
+        vm_write!(f, "({}, {}, {})", self.x, self.y, self.z)
+    }
+
+    #[rune::function(protocol = DEBUG_FMT)]
+    pub fn debug_fmt(&self, f: &mut Formatter) -> VmResult<()> {
+        vm_write!(f, "{:?}", self)
+    }
+}
+
 #[derive(Any)]
 #[rune(item = ::rune)]
 #[derive(PartialEq, Debug, Deserialize, TryClone)]
@@ -60,6 +211,66 @@ pub struct ScriptableItem {
     pub tags: rune::alloc::Vec<String>,
     #[rune(get)]
     pub count: i32,
+    #[rune(get)]
+    pub max_stack_size: i32,
+    /// damage taken so far, 0 for fresh tools and anything that can't be damaged
+    #[rune(get)]
+    pub damage: i32,
+    /// 0 if the item can't be damaged
+    #[rune(get)]
+    pub max_damage: i32,
+    #[rune(get)]
+    pub custom_name: Option<String>,
+    #[rune(get)]
+    pub enchantments: rune::alloc::Vec<ScriptableEnchantment>,
+    /// enchantments stored on enchanted books, these don't apply to the item itself
+    #[rune(get)]
+    pub stored_enchantments: rune::alloc::Vec<ScriptableEnchantment>,
+    /// every data component on the stack, values are encoded as snbt
+    #[rune(get)]
+    pub components: rune::alloc::Vec<ScriptableComponent>,
+}
+
+impl ScriptableItem {
+    /// uses left before the item breaks, None if it can't be damaged
+    #[rune::function(keep)]
+    fn durability(&self) -> Option<i32> {
+        (self.max_damage > 0).then(|| self.max_damage - self.damage)
+    }
+
+    #[rune::function(keep)]
+    fn is_damaged(&self) -> bool {
+        self.max_damage > 0 && self.damage > 0
+    }
+
+    /// snbt value of the data component with the given id, e.g. `minecraft:custom_data`
+    #[rune::function(keep)]
+    fn component(&self, id: &str) -> Option<String> {
+        self.components
+            .iter()
+            .find(|component| component.id == id)
+            .map(|component| component.value.clone())
+    }
+}
+
+#[derive(Any)]
+#[rune(item = ::rune)]
+#[derive(PartialEq, Debug, Deserialize, TryClone)]
+pub struct ScriptableEnchantment {
+    #[rune(get)]
+    pub id: String,
+    #[rune(get)]
+    pub level: i32,
+}
+
+#[derive(Any)]
+#[rune(item = ::rune)]
+#[derive(PartialEq, Debug, Deserialize, TryClone)]
+pub struct ScriptableComponent {
+    #[rune(get)]
+    pub id: String,
+    #[rune(get)]
+    pub value: String,
 }
 
 #[derive(Any)]
@@ -67,6 +278,11 @@ pub struct ScriptableItem {
 #[derive(PartialEq, Debug, Deserialize, TryClone)]
 pub struct ScriptableBlockEntity {
     raw_access_index: u32,
+    // generation of the block context this entity was handed out with, `raw_access_index` only
+    // means something for that context
+    #[serde(skip)]
+    generation: u64,
+    #[rune(get)]
     pub block_pos: BlockPos,
     #[rune(get)]
     pub dimension: String,
@@ -74,11 +290,48 @@ pub struct ScriptableBlockEntity {
     pub name: String,
     #[rune(get)]
     pub items: rune::alloc::Vec<ScriptableItem>,
+    /// number of item slots, including empty ones which aren't in `items`
+    #[rune(get)]
+    pub slots: i32,
+    #[rune(get)]
+    pub tanks: rune::alloc::Vec<ScriptableFluidTank>,
+    /// None if the entity has no energy storage
+    #[rune(get)]
+    pub energy: Option<ScriptableEnergy>,
 }
 
 #[derive(Any)]
 #[rune(item = ::rune)]
-#[derive(PartialEq, Debug, TryClone)]
+#[derive(PartialEq, Debug, Deserialize, TryClone)]
+pub struct ScriptableFluidTank {
+    tank_index: u32,
+    /// `minecraft:empty` if nothing is in the tank
+    #[rune(get)]
+    pub fluid: String,
+    /// in millibuckets
+    #[rune(get)]
+    pub amount: i32,
+    #[rune(get)]
+    pub capacity: i32,
+}
+
+#[derive(Any)]
+#[rune(item = ::rune)]
+#[derive(PartialEq, Debug, Deserialize, TryClone)]
+pub struct ScriptableEnergy {
+    #[rune(get)]
+    pub stored: i32,
+    #[rune(get)]
+    pub capacity: i32,
+    #[rune(get)]
+    pub can_extract: bool,
+    #[rune(get)]
+    pub can_receive: bool,
+}
+
+#[derive(Any)]
+#[rune(item = ::rune)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, TryClone)]
 pub enum Direction {
     #[rune(constructor)]
     Down,
@@ -94,7 +347,155 @@ pub enum Direction {
     East,
 }
 
+#[derive(Any)]
+#[rune(item = ::rune)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug, TryClone)]
+pub enum Axis {
+    #[rune(constructor)]
+    X,
+    #[rune(constructor)]
+    Y,
+    #[rune(constructor)]
+    Z,
+}
+
+impl Axis {
+    #[rune::function(protocol = PARTIAL_EQ)]
+    fn partial_eq(&self, other: &Axis) -> bool {
+        self == other
+    }
+
+    /// lowercase like `Direction`, `x`/`y`/`z`
+    #[rune::function(protocol = DISPLAY_FMT)]
+    pub fn display_fmt(&self, f: &mut Formatter) -> VmResult<()> {
+        let name = match self {
+            Axis::X => "x",
+            Axis::Y => "y",
+            Axis::Z => "z",
+        };
+
+        vm_write!(f, "{}", name)
+    }
+
+    #[rune::function(protocol = DEBUG_FMT)]
+    pub fn debug_fmt(&self, f: &mut Formatter) -> VmResult<()> {
+        vm_write!(f, "{:?}", self)
+    }
+}
+
 impl Direction {
+    const ALL: [Direction; 6] = [
+        Direction::Down,
+        Direction::Up,
+        Direction::North,
+        Direction::South,
+        Direction::West,
+        Direction::East,
+    ];
+
+    /// every direction, in the same order as minecraft's
+    #[rune::function(path = Self::all)]
+    fn all() -> Vec<Direction> {
+        Self::ALL.to_vec()
+    }
+
+    /// parses `north`, `NORTH`, ...
+    #[rune::function(path = Self::from_name)]
+    fn from_name(name: &str) -> Option<Direction> {
+        Self::ALL
+            .into_iter()
+            .find(|face| face.to_str().eq_ignore_ascii_case(name))
+    }
+
+    #[rune::function]
+    fn opposite(&self) -> Direction {
+        match self {
+            Direction::Down => Direction::Up,
+            Direction::Up => Direction::Down,
+            Direction::North => Direction::South,
+            Direction::South => Direction::North,
+            Direction::West => Direction::East,
+            Direction::East => Direction::West,
+        }
+    }
+
+    /// rotates a quarter turn clockwise looking down `axis`, directions along the axis
+    /// stay where they are
+    #[rune::function]
+    fn rotate_clockwise(&self, axis: Axis) -> Direction {
+        use Direction::*;
+
+        match (axis, self) {
+            (Axis::X, Down) => South,
+            (Axis::X, South) => Up,
+            (Axis::X, Up) => North,
+            (Axis::X, North) => Down,
+            (Axis::Y, North) => East,
+            (Axis::Y, East) => South,
+            (Axis::Y, South) => West,
+            (Axis::Y, West) => North,
+            (Axis::Z, Down) => West,
+            (Axis::Z, West) => Up,
+            (Axis::Z, Up) => East,
+            (Axis::Z, East) => Down,
+            (_, face) => *face,
+        }
+    }
+
+    #[rune::function]
+    fn axis(&self) -> Axis {
+        match self {
+            Direction::Down | Direction::Up => Axis::Y,
+            Direction::North | Direction::South => Axis::Z,
+            Direction::West | Direction::East => Axis::X,
+        }
+    }
+
+    /// the step one block towards this direction
+    #[rune::function]
+    fn to_offset(&self) -> BlockPos {
+        let (x, y, z) = self.delta();
+        BlockPos { x, y, z }
+    }
+
+    #[rune::function(protocol = PARTIAL_EQ)]
+    fn partial_eq(&self, other: &Direction) -> bool {
+        self == other
+    }
+
+    #[rune::function(protocol = DISPLAY_FMT)]
+    pub fn display_fmt(&self, f: &mut Formatter) -> VmResult<()> {
+        vm_write!(f, "{}", self.to_str().to_lowercase())
+    }
+
+    #[rune::function(protocol = DEBUG_FMT)]
+    pub fn debug_fmt(&self, f: &mut Formatter) -> VmResult<()> {
+        vm_write!(f, "{:?}", self)
+    }
+
+    fn delta(&self) -> (i32, i32, i32) {
+        match self {
+            Direction::Down => (0, -1, 0),
+            Direction::Up => (0, 1, 0),
+            Direction::North => (0, 0, -1),
+            Direction::South => (0, 0, 1),
+            Direction::West => (-1, 0, 0),
+            Direction::East => (1, 0, 0),
+        }
+    }
+
+    // matches the ordinal of net.minecraft.core.Direction
+    fn ordinal(&self) -> usize {
+        match self {
+            Direction::Down => 0,
+            Direction::Up => 1,
+            Direction::North => 2,
+            Direction::South => 3,
+            Direction::West => 4,
+            Direction::East => 5,
+        }
+    }
+
     fn to_str(&self) -> &str {
         match self {
             Direction::Down => "DOWN",
@@ -108,6 +509,81 @@ impl Direction {
 }
 
 impl ScriptableBlockEntity {
+    #[rune::function]
+    fn items_matching(&self, filter: &Filter) -> VmResult<Vec<ScriptableItem>> {
+        let mut items = Vec::new();
+
+        for item in self.items.iter().filter(|item| filter.test(item)) {
+            items.push(vm_try!(item.try_clone()));
+        }
+
+        VmResult::Ok(items)
+    }
+
+    fn count_matching(&self, filter: &Filter) -> i64 {
+        self.items
+            .iter()
+            .filter(|item| filter.test(item))
+            .map(|item| item.count as i64)
+            .sum()
+    }
+
+    fn add_totals(&self, totals: &mut HashMap<String, i64>) {
+        for item in self.items.iter() {
+            *totals.entry(item.name.clone()).or_default() += item.count as i64;
+        }
+    }
+
+    fn add_totals_by_tag(&self, totals: &mut HashMap<String, i64>) {
+        for item in self.items.iter() {
+            for tag in item.tags.iter() {
+                *totals.entry(tag.clone()).or_default() += item.count as i64;
+            }
+        }
+    }
+
+    /// number of items across every stack matching `filter`
+    #[rune::function]
+    fn count_of(&self, filter: &Filter) -> i64 {
+        self.count_matching(filter)
+    }
+
+    /// item id to the number of those items held
+    #[rune::function]
+    fn totals(&self) -> HashMap<String, i64> {
+        let mut totals = HashMap::new();
+        self.add_totals(&mut totals);
+        totals
+    }
+
+    /// tag to the number of items with that tag held
+    #[rune::function]
+    fn totals_by_tag(&self) -> HashMap<String, i64> {
+        let mut totals = HashMap::new();
+        self.add_totals_by_tag(&mut totals);
+        totals
+    }
+
+    /// the stack in the lowest slot matching `filter`
+    #[rune::function]
+    fn first_slot(&self, filter: &Filter) -> VmResult<Option<ScriptableItem>> {
+        let item = self
+            .items
+            .iter()
+            .filter(|item| filter.test(item))
+            .min_by_key(|item| item.slot_index);
+
+        VmResult::Ok(match item {
+            Some(item) => Some(vm_try!(item.try_clone())),
+            None => None,
+        })
+    }
+
+    #[rune::function]
+    fn free_slots(&self) -> i64 {
+        (self.slots as i64 - self.items.len() as i64).max(0)
+    }
+
     #[rune::function(protocol = DISPLAY_FMT)]
     pub fn display_fmt(&self, f: &mut Formatter) -> VmResult<()> {
         // todo lol
@@ -120,12 +596,23 @@ impl ScriptableBlockEntity {
     }
 }
 
-#[derive(Any)]
+// bumped every time a JNIBlockContext is created. the env and local refs are only valid for
+// the native call that created them, so a context kept alive across a `yield` must not touch them.
+static BLOCK_CONTEXT_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Any, Clone)]
 #[rune(item = ::rune)]
 pub struct JNIBlockContext {
     raw_env: NonNull<jni::sys::JNIEnv>,
     block_entity: NonNull<jni::sys::_jobject>,
     raw_scriptable_entities: NonNull<jni::sys::_jobject>,
+    // entities past the end of the array were found by `block_entities` and live on the kotlin side
+    raw_len: jsize,
+    generation: u64,
+    /// persistent key/value store of the script being run, owned by the RuneheartContext
+    #[rune(get)]
+    state: Value,
+    transfers: TransferQueue,
 }
 
 impl JNIBlockContext {
@@ -133,15 +620,47 @@ impl JNIBlockContext {
         env: &JNIEnv,
         block_entity: &JObject,
         raw_scriptable_entities: &JObjectArray,
+        transfers: TransferQueue,
     ) -> Self {
         Self {
             // unwrap: get_raw is assumed non-null
             raw_env: NonNull::new(env.get_raw()).unwrap(),
             block_entity: NonNull::new(block_entity.as_raw()).unwrap(),
             raw_scriptable_entities: NonNull::new(raw_scriptable_entities.as_raw()).unwrap(),
+            raw_len: env.get_array_length(raw_scriptable_entities).unwrap_or(0),
+            generation: BLOCK_CONTEXT_GENERATION.fetch_add(1, Ordering::Relaxed) + 1,
+            state: Value::empty(),
+            transfers,
         }
     }
 
+    /// the same context handed to a single script, with that script's state
+    pub fn with_state(&self, state: Value) -> Self {
+        Self {
+            state,
+            ..self.clone()
+        }
+    }
+
+    fn is_live(&self) -> bool {
+        self.generation == BLOCK_CONTEXT_GENERATION.load(Ordering::Relaxed)
+    }
+
+    /// ties freshly decoded entities to this context so they can't be used with a later one
+    pub fn claim(&self, mut entities: Vec<ScriptableBlockEntity>) -> Vec<ScriptableBlockEntity> {
+        for entity in entities.iter_mut() {
+            entity.generation = self.generation;
+        }
+
+        entities
+    }
+
+    // the context has to be the current one and the entity has to come from it, an entity kept
+    // across a `yield` has a raw index into an older tick's array
+    fn owns(&self, entity: &ScriptableBlockEntity) -> bool {
+        self.is_live() && entity.generation == self.generation
+    }
+
     fn env(&self) -> JNIEnv<'_> {
         // unsafe/unwrap: self.env is assumed non-null
         unsafe { JNIEnv::from_raw(self.raw_env.as_ptr()) }.unwrap()
@@ -157,48 +676,344 @@ impl JNIBlockContext {
 
     fn get_raw_scriptable_entity(&self, index: u32) -> Option<JObject> {
         let mut env = self.env();
-        let raw = self.raw_scriptable_entities();
 
-        env.get_object_array_element(raw, index as jsize).ok()
+        if (index as jsize) < self.raw_len {
+            let raw = self.raw_scriptable_entities();
+            return env.get_object_array_element(raw, index as jsize).ok();
+        }
+
+        let method = bindings::get(&mut env).ok()?.raw_scriptable_entity;
+        // safety: raw_scriptable_entity was resolved as `(I)Lrose/runeheart/blockentity/RawScriptableBlockEntity;`
+        let raw = unsafe {
+            env.call_method_unchecked(
+                self.block_entity(),
+                method,
+                ReturnType::Object,
+                &[JValue::Int(index as jint).as_jni()],
+            )
+        }
+        .and_then(|value| value.l());
+
+        match raw {
+            Ok(raw) if !raw.is_null() => Some(raw),
+            Ok(_) => None,
+            Err(_) => {
+                clear_exception(&mut env);
+                None
+            }
+        }
+    }
+
+    fn bindings(&self) -> Result<&'static JniBindings, TransferError> {
+        Ok(bindings::get(&mut self.env())?)
+    }
+
+    fn direction(&self, face: &Direction) -> Result<&'static JObject<'static>, TransferError> {
+        Ok(self.bindings()?.directions[face.ordinal()].as_obj())
     }
 
+    // the kotlin side takes a nullable Integer, null meaning "as much as possible"
+    fn boxed_amount(&self, amount: Option<i32>) -> Result<JObject<'_>, TransferError> {
+        let Some(amount) = amount else {
+            return Ok(JObject::null());
+        };
+
+        let bindings = self.bindings()?;
+        // safety: integer_value_of was resolved as `(I)Ljava/lang/Integer;`
+        let boxed = unsafe {
+            self.env().call_static_method_unchecked(
+                bindings.integer_class(),
+                bindings.integer_value_of,
+                ReturnType::Object,
+                &[JValue::Int(amount as jint).as_jni()],
+            )
+        }?;
+
+        Ok(boxed.l()?)
+    }
+
+    fn raw_entity(&self, entity: &ScriptableBlockEntity) -> Result<JObject<'_>, TransferError> {
+        // entities handed out on an earlier tick can't be reached anymore
+        if !self.owns(entity) {
+            return Err(TransferError::EntityGone);
+        }
+
+        self.get_raw_scriptable_entity(entity.raw_access_index)
+            .ok_or(TransferError::EntityGone)
+    }
+
+    /// calls one of the kotlin transfer methods, they return the amount moved or a negative
+    /// code, see `TransferError::from_code`
+    fn call_transfer(&self, method: JMethodID, args: &[JValue]) -> Result<i32, TransferError> {
+        let mut env = self.env();
+        let args: Vec<jvalue> = args.iter().map(JValue::as_jni).collect();
+
+        // safety: every transfer method id is resolved with a signature returning `I`, and the
+        // callers pass arguments matching it
+        let result = unsafe {
+            env.call_method_unchecked(
+                self.block_entity(),
+                method,
+                ReturnType::Primitive(Primitive::Int),
+                &args,
+            )
+        }
+        .and_then(|value| value.i());
+
+        match result {
+            Ok(code) => TransferError::from_code(code),
+            Err(err) => {
+                clear_exception(&mut env);
+                Err(err.into())
+            }
+        }
+    }
+
+    /// queues a `move_item` to run after every script has ticked, all queued moves go to kotlin
+    /// in one call. returns a ticket to look the result up with `transfer_result` next tick
     #[rune::function]
-    fn move_item(
+    fn queue_move(
         &self,
         src: &ScriptableBlockEntity,
         dst: &ScriptableBlockEntity,
         item: &ScriptableItem,
         face: Direction,
         amount: Option<i32>,
-    ) -> Option<()> {
+    ) -> Result<u64, TransferError> {
+        // raw indices from an earlier tick would point at whatever is in that slot now
+        if !self.owns(src) || !self.owns(dst) {
+            return Err(TransferError::EntityGone);
+        }
+
+        Ok(self.transfers.push(
+            src.raw_access_index,
+            dst.raw_access_index,
+            item.slot_index,
+            face.to_str(),
+            amount,
+        ))
+    }
+
+    /// block entities around this block matching `query`, closest first. `Single` returns at
+    /// most one, `Multi` at most `query.limit` and `All` every match. the returned entities can
+    /// be used with the move functions for the rest of the tick
+    #[rune::function]
+    fn block_entities(
+        &self,
+        target: BlockEntityTarget,
+        query: &BlockEntityQuery,
+    ) -> Result<Vec<ScriptableBlockEntity>, Error> {
+        if !self.is_live() {
+            return Err(Error::new("block context used after its tick"));
+        }
+
         let mut env = self.env();
+        let method = bindings::get(&mut env)
+            .map_err(|err| Error::new(err.to_string()))?
+            .query_block_entities;
+        let radius = query.radius.clamp(0, MAX_QUERY_RADIUS);
+        let limit = match target {
+            BlockEntityTarget::Single => 1,
+            BlockEntityTarget::Multi => query.limit,
+            BlockEntityTarget::All => usize::MAX,
+        };
+        let name = match &query.name {
+            Some(name) => env
+                .new_string(name)
+                .map_err(|err| Error::new(err.to_string()))?
+                .into(),
+            None => JObject::null(),
+        };
 
-        let src_raw = self.get_raw_scriptable_entity(src.raw_access_index)?;
-        let dst_raw = self.get_raw_scriptable_entity(dst.raw_access_index)?;
+        // kotlin filters and cuts to the limit before serializing anything, a `Single` over a
+        // full radius must not encode every entity in range
+        // safety: query_block_entities was resolved as `(ILjava/lang/String;ZI)[B`
+        let bytes = match unsafe {
+            env.call_method_unchecked(
+                self.block_entity(),
+                method,
+                ReturnType::Array,
+                &[
+                    JValue::Int(radius as jint).as_jni(),
+                    JValue::Object(&name).as_jni(),
+                    JValue::Bool(query.has_items as jboolean).as_jni(),
+                    JValue::Int(limit.min(jint::MAX as usize) as jint).as_jni(),
+                ],
+            )
+        }
+        .and_then(|value| value.l())
+        .and_then(|bytes| env.convert_byte_array(JByteArray::from(bytes)))
+        {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                clear_exception(&mut env);
+                return Err(Error::new(err.to_string()));
+            }
+        };
+
+        let found = ciborium::from_reader::<Vec<ScriptableBlockEntity>, _>(&bytes[..])
+            .map_err(|err| Error::new(err.to_string()))?;
+
+        Ok(self.claim(found))
+    }
+
+    /// result of a move queued last tick, None if the ticket is unknown or older than that
+    #[rune::function]
+    fn transfer_result(&self, ticket: u64) -> Option<Result<i32, TransferError>> {
+        self.transfers.result(ticket)
+    }
+
+    fn call_move_items(&self, transfers: &[QueuedTransfer]) -> Result<Vec<jint>, TransferError> {
+        let mut encoded = Vec::new();
+        ciborium::into_writer(transfers, &mut encoded)
+            .map_err(|err| TransferError::JniFailure(err.to_string()))?;
+
+        let move_items = self.bindings()?.move_items;
+        let mut env = self.env();
+        let bytes = env.byte_array_from_slice(&encoded)?;
+
+        // safety: move_items was resolved as `([B)[I`
+        let codes = match unsafe {
+            env.call_method_unchecked(
+                self.block_entity(),
+                move_items,
+                ReturnType::Array,
+                &[JValue::Object(&bytes).as_jni()],
+            )
+        }
+        .and_then(|value| value.l())
+        {
+            Ok(codes) => JIntArray::from(codes),
+            Err(err) => {
+                clear_exception(&mut env);
+                return Err(err.into());
+            }
+        };
 
-        let dir_cls = env.find_class("net/minecraft/core/Direction").ok()?;
-        let face = env
-            .get_static_field(dir_cls, face.to_str(), "Lnet/minecraft/core/Direction;")
-            .ok()?
-            .l()
-            .ok()?;
+        let mut buf = vec![0; env.get_array_length(&codes)? as usize];
+        env.get_int_array_region(&codes, 0, &mut buf)?;
 
-        let amount = if let Some(amount) = amount {
-            env.new_object("java/lang/Integer", "(I)V", &[JValue::Int(amount as jint)])
-                .ok()?
+        Ok(buf)
+    }
+
+    /// runs everything queued with `queue_move` this tick, the results replace the ones from
+    /// the previous flush
+    pub fn flush_transfers(&self) {
+        let pending = self.transfers.take_pending();
+        let codes = if pending.is_empty() {
+            Ok(Vec::new())
         } else {
-            JObject::null()
+            self.call_move_items(&pending)
         };
 
-        env.call_method(
-            self.block_entity(),
-            "moveItem",
-            "(Lrose/runeheart/blockentity/RawScriptableBlockEntity;Lrose/runeheart/blockentity/RawScriptableBlockEntity;ILnet/minecraft/core/Direction;Ljava/lang/Integer;)V",
-            &[JValue::Object(&src_raw),JValue::Object(&dst_raw), JValue::Int(item.slot_index as jint), JValue::Object(&face), JValue::Object(&amount)],
+        self.transfers.complete(transfer::results(&pending, codes));
+    }
+
+    fn transfer_item(
+        &self,
+        src: &ScriptableBlockEntity,
+        dst: &ScriptableBlockEntity,
+        item: &ScriptableItem,
+        face: &Direction,
+        amount: Option<i32>,
+        simulate: bool,
+    ) -> Result<i32, TransferError> {
+        let src_raw = self.raw_entity(src)?;
+        let dst_raw = self.raw_entity(dst)?;
+        let face = self.direction(face)?;
+        let amount = self.boxed_amount(amount)?;
+
+        self.call_transfer(
+            self.bindings()?.move_item,
+            &[JValue::Object(&src_raw),JValue::Object(&dst_raw), JValue::Int(item.slot_index as jint), JValue::Object(face), JValue::Object(&amount), JValue::Bool(simulate as jboolean)],
+        )
+    }
+
+    /// moves `item` out of `src` into `dst`, returns how many items actually moved
+    #[rune::function]
+    fn move_item(
+        &self,
+        src: &ScriptableBlockEntity,
+        dst: &ScriptableBlockEntity,
+        item: &ScriptableItem,
+        face: Direction,
+        amount: Option<i32>,
+    ) -> Result<i32, TransferError> {
+        self.transfer_item(src, dst, item, &face, amount, false)
+    }
+
+    /// how many items `move_item` would move with the same arguments, without moving anything
+    #[rune::function]
+    fn simulate_move(
+        &self,
+        src: &ScriptableBlockEntity,
+        dst: &ScriptableBlockEntity,
+        item: &ScriptableItem,
+        face: Direction,
+        amount: Option<i32>,
+    ) -> Result<i32, TransferError> {
+        self.transfer_item(src, dst, item, &face, amount, true)
+    }
+
+    /// `simulate_move` against every entity in `dsts`, results are in the same order
+    #[rune::function]
+    fn simulate_moves(
+        &self,
+        src: &ScriptableBlockEntity,
+        dsts: Vec<Value>,
+        item: &ScriptableItem,
+        face: Direction,
+        amount: Option<i32>,
+    ) -> VmResult<Vec<Result<i32, TransferError>>> {
+        let mut results = Vec::with_capacity(dsts.len());
+
+        for dst in dsts {
+            let dst = vm_try!(dst.borrow_ref::<ScriptableBlockEntity>());
+            results.push(self.transfer_item(src, &dst, item, &face, amount, true));
+        }
+
+        VmResult::Ok(results)
+    }
+
+    /// moves fluid out of `tank` on `src` into whatever tanks `dst` exposes on `face`,
+    /// returns the millibuckets moved
+    #[rune::function]
+    fn move_fluid(
+        &self,
+        src: &ScriptableBlockEntity,
+        dst: &ScriptableBlockEntity,
+        tank: &ScriptableFluidTank,
+        face: Direction,
+        amount: Option<i32>,
+    ) -> Result<i32, TransferError> {
+        let src_raw = self.raw_entity(src)?;
+        let dst_raw = self.raw_entity(dst)?;
+        let face = self.direction(&face)?;
+        let amount = self.boxed_amount(amount)?;
+
+        self.call_transfer(
+            self.bindings()?.move_fluid,
+            &[JValue::Object(&src_raw), JValue::Object(&dst_raw), JValue::Int(tank.tank_index as jint), JValue::Object(face), JValue::Object(&amount)],
         )
-        .ok()?;
+    }
 
-        Some(())
+    #[rune::function]
+    fn move_energy(
+        &self,
+        src: &ScriptableBlockEntity,
+        dst: &ScriptableBlockEntity,
+        face: Direction,
+        amount: Option<i32>,
+    ) -> Result<i32, TransferError> {
+        let src_raw = self.raw_entity(src)?;
+        let dst_raw = self.raw_entity(dst)?;
+        let face = self.direction(&face)?;
+        let amount = self.boxed_amount(amount)?;
+
+        self.call_transfer(
+            self.bindings()?.move_energy,
+            &[JValue::Object(&src_raw), JValue::Object(&dst_raw), JValue::Object(face), JValue::Object(&amount)],
+        )
     }
 }
 
@@ -210,6 +1025,12 @@ struct Error {
 }
 
 impl Error {
+    fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+        }
+    }
+
     #[rune::function(protocol = DISPLAY_FMT)]
     pub fn display_fmt(&self, f: &mut Formatter) -> VmResult<()> {
         vm_write!(f, "{}", self.message)
@@ -221,6 +1042,68 @@ impl Error {
     }
 }
 
+// a thrown exception stays pending and would abort the next jni call
+fn clear_exception(env: &mut JNIEnv) {
+    if env.exception_check().unwrap_or(false) {
+        let _ = env.exception_clear();
+    }
+}
+
+/// why a transfer between two block entities didn't happen
+#[derive(Any)]
+#[rune(item = ::rune)]
+#[derive(PartialEq, Debug, Clone, TryClone)]
+pub enum TransferError {
+    #[rune(constructor)]
+    NoSuchSlot,
+    #[rune(constructor)]
+    DestinationFull,
+    #[rune(constructor)]
+    EntityGone,
+    #[rune(constructor)]
+    JniFailure(String),
+}
+
+impl TransferError {
+    // mirrors the MOVE_* constants in ExampleBlockEntity
+    fn from_code(code: i32) -> Result<i32, Self> {
+        match code {
+            moved if moved >= 0 => Ok(moved),
+            -1 => Err(Self::NoSuchSlot),
+            -2 => Err(Self::DestinationFull),
+            -3 => Err(Self::EntityGone),
+            code => Err(Self::JniFailure(format!("unknown transfer result {code}"))),
+        }
+    }
+
+    #[rune::function(protocol = DISPLAY_FMT)]
+    pub fn display_fmt(&self, f: &mut Formatter) -> VmResult<()> {
+        vm_write!(f, "{}", self)
+    }
+
+    #[rune::function(protocol = DEBUG_FMT)]
+    pub fn debug_fmt(&self, f: &mut Formatter) -> VmResult<()> {
+        vm_write!(f, "{:?}", self)
+    }
+}
+
+impl fmt::Display for TransferError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransferError::NoSuchSlot => write!(f, "no such slot"),
+            TransferError::DestinationFull => write!(f, "destination is full"),
+            TransferError::EntityGone => write!(f, "block entity is gone"),
+            TransferError::JniFailure(message) => write!(f, "jni failure: {}", message),
+        }
+    }
+}
+
+impl From<jni::errors::Error> for TransferError {
+    fn from(err: jni::errors::Error) -> Self {
+        TransferError::JniFailure(err.to_string())
+    }
+}
+
 #[derive(Any)]
 #[rune(item = ::rune)]
 enum BlockEntityTarget {
@@ -232,6 +1115,40 @@ enum BlockEntityTarget {
     All,
 }
 
+const MAX_QUERY_RADIUS: i32 = 16;
+
+/// what `JNIBlockContext::block_entities` looks for, every unset filter matches anything. there is
+/// no dimension filter, a query only ever searches the level this block is in
+#[derive(Any)]
+#[rune(item = ::rune)]
+#[derive(PartialEq, Debug)]
+pub struct BlockEntityQuery {
+    /// in blocks along each axis, clamped to `MAX_QUERY_RADIUS`
+    #[rune(get, set)]
+    pub radius: i32,
+    /// block entity type, e.g. `minecraft:chest`
+    #[rune(get, set)]
+    pub name: Option<String>,
+    /// only entities holding at least one item
+    #[rune(get, set)]
+    pub has_items: bool,
+    /// how many entities `Multi` returns at most
+    #[rune(get, set)]
+    pub limit: usize,
+}
+
+impl BlockEntityQuery {
+    #[rune::function(path = Self::new)]
+    fn new() -> Self {
+        Self {
+            radius: 8,
+            name: None,
+            has_items: false,
+            limit: 16,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #[rune::function]
@@ -246,15 +1163,309 @@ mod tests {
     #[test]
     fn test_module() {
         let mut context = RuneheartContext::new().unwrap();
-        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"))
             .join("src")
             .join("script")
-            .join("rune_module")
-            .join("test.rn");
-        context.set_active_script(SourceKind::Path(path)).unwrap();
+            .join("rune_module");
+        context.set_scripts_root(Some(root.clone()));
+        context.set_active_script(SourceKind::Path(root.join("test.rn"))).unwrap();
 
         let result = context.callback_tick_test().unwrap();
         let resultant = rune::from_value::<Result<u64, Error>>(result).unwrap();
         assert_eq!(resultant, Ok(123));
     }
+
+    #[test]
+    fn test_block_pos() {
+        let mut context = RuneheartContext::new().unwrap();
+        context
+            .set_active_script(SourceKind::Content(
+                r#"
+                use std::collections::HashMap;
+
+                pub fn tick() {
+                    let a = rune::BlockPos::new(1, 2, 3);
+                    let b = a + rune::BlockPos::new(1, 1, 1);
+                    let north = a.offset(rune::Direction::North, Some(2));
+
+                    let seen = HashMap::new();
+                    seen.insert(a, "a");
+
+                    [
+                        b.x, b.y, b.z,
+                        north.z,
+                        a.manhattan_distance(b),
+                        seen.contains_key(rune::BlockPos::new(1, 2, 3)),
+                        a == b - rune::BlockPos::new(1, 1, 1),
+                        `${a}`,
+                    ]
+                }
+                "#
+                .to_string(),
+            ))
+            .unwrap();
+
+        let result = context.callback_tick_test().unwrap();
+        let result = rune::from_value::<Vec<Value>>(result).unwrap();
+        let ints = result[..5]
+            .iter()
+            .map(|value| rune::from_value::<i64>(value.clone()).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(ints, vec![2, 3, 4, 1, 3]);
+        assert!(rune::from_value::<bool>(result[5].clone()).unwrap());
+        assert!(rune::from_value::<bool>(result[6].clone()).unwrap());
+        assert_eq!(rune::from_value::<String>(result[7].clone()).unwrap(), "(1, 2, 3)");
+    }
+
+    #[test]
+    fn test_block_pos_extremes() {
+        let mut context = RuneheartContext::new().unwrap();
+        context
+            .set_active_script(SourceKind::Content(
+                r#"
+                pub fn tick() {
+                    let max = rune::BlockPos::new(2147483647, 0, 0);
+                    let min = rune::BlockPos::new(-2147483648, 0, 0);
+
+                    [
+                        (max + rune::BlockPos::new(1, 0, 0)).x,
+                        (min - rune::BlockPos::new(1, 0, 0)).x,
+                        max.offset(rune::Direction::East, Some(2147483647)).x,
+                        max.distance(min),
+                        max.manhattan_distance(min),
+                    ]
+                }
+                "#
+                .to_string(),
+            ))
+            .unwrap();
+
+        let result = context.callback_tick_test().unwrap();
+        let result = rune::from_value::<Vec<Value>>(result).unwrap();
+
+        assert_eq!(rune::from_value::<i64>(result[0].clone()).unwrap(), i32::MIN as i64);
+        assert_eq!(rune::from_value::<i64>(result[1].clone()).unwrap(), i32::MAX as i64);
+        assert_eq!(rune::from_value::<i64>(result[2].clone()).unwrap(), -2);
+        assert_eq!(rune::from_value::<f64>(result[3].clone()).unwrap(), u32::MAX as f64);
+        assert_eq!(rune::from_value::<i64>(result[4].clone()).unwrap(), u32::MAX as i64);
+    }
+
+    fn item(slot_index: u32, name: &str, tags: &[&str], count: i32) -> ScriptableItem {
+        let mut item_tags = rune::alloc::Vec::new();
+        for tag in tags {
+            item_tags.try_push(tag.to_string()).unwrap();
+        }
+
+        ScriptableItem {
+            slot_index,
+            name: name.to_string(),
+            tags: item_tags,
+            count,
+            max_stack_size: 64,
+            damage: 0,
+            max_damage: 0,
+            custom_name: None,
+            enchantments: rune::alloc::Vec::new(),
+            stored_enchantments: rune::alloc::Vec::new(),
+            components: rune::alloc::Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_inventory_totals() {
+        let mut items = rune::alloc::Vec::new();
+        items
+            .try_push(item(0, "minecraft:coal", &["minecraft:coals"], 10))
+            .unwrap();
+        items
+            .try_push(item(3, "minecraft:charcoal", &["minecraft:coals"], 5))
+            .unwrap();
+        items.try_push(item(4, "minecraft:coal", &[], 1)).unwrap();
+
+        let entity = ScriptableBlockEntity {
+            raw_access_index: 0,
+            generation: 0,
+            block_pos: BlockPos { x: 0, y: 0, z: 0 },
+            dimension: "minecraft:overworld".to_string(),
+            name: "minecraft:chest".to_string(),
+            items,
+            slots: 27,
+            tanks: rune::alloc::Vec::new(),
+            energy: None,
+        };
+
+        let mut totals = HashMap::new();
+        entity.add_totals(&mut totals);
+        assert_eq!(totals.get("minecraft:coal"), Some(&11));
+        assert_eq!(totals.get("minecraft:charcoal"), Some(&5));
+
+        let mut by_tag = HashMap::new();
+        entity.add_totals_by_tag(&mut by_tag);
+        assert_eq!(by_tag.get("minecraft:coals"), Some(&15));
+    }
+
+    #[test]
+    fn test_transfer_error_from_code() {
+        assert_eq!(TransferError::from_code(0), Ok(0));
+        assert_eq!(TransferError::from_code(64), Ok(64));
+        assert_eq!(TransferError::from_code(-1), Err(TransferError::NoSuchSlot));
+        assert_eq!(TransferError::from_code(-2), Err(TransferError::DestinationFull));
+        assert_eq!(TransferError::from_code(-3), Err(TransferError::EntityGone));
+        assert!(matches!(TransferError::from_code(-4), Err(TransferError::JniFailure(_))));
+    }
+
+    // field names as ExampleBlockEntity.kt writes them, a rename on either side breaks this
+    #[test]
+    fn test_decode_entity_with_tanks_and_energy() {
+        let entity = |tanks: Vec<ciborium::Value>, energy: ciborium::Value| {
+            ciborium::cbor!({
+                "raw_access_index" => 2,
+                "block_pos" => { "x" => 1, "y" => -60, "z" => 3 },
+                "dimension" => "minecraft:overworld",
+                "name" => "minecraft:cauldron",
+                "items" => [],
+                "slots" => 0,
+                "tanks" => tanks,
+                "energy" => energy,
+            })
+            .unwrap()
+        };
+        let tank = ciborium::cbor!({
+            "tank_index" => 0,
+            "fluid" => "minecraft:water",
+            "amount" => 250,
+            "capacity" => 1000,
+        })
+        .unwrap();
+        let energy = ciborium::cbor!({
+            "stored" => 400,
+            "capacity" => 10000,
+            "can_extract" => true,
+            "can_receive" => false,
+        })
+        .unwrap();
+        let entities = vec![
+            entity(vec![tank], energy),
+            entity(vec![], ciborium::Value::Null),
+        ];
+
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&entities, &mut bytes).unwrap();
+        let decoded = ciborium::from_reader::<Vec<ScriptableBlockEntity>, _>(&bytes[..]).unwrap();
+
+        assert_eq!(decoded[0].raw_access_index, 2);
+        assert_eq!(decoded[0].block_pos, BlockPos { x: 1, y: -60, z: 3 });
+        assert_eq!(
+            decoded[0].tanks[0],
+            ScriptableFluidTank {
+                tank_index: 0,
+                fluid: "minecraft:water".to_string(),
+                amount: 250,
+                capacity: 1000,
+            }
+        );
+        assert_eq!(
+            decoded[0].energy,
+            Some(ScriptableEnergy {
+                stored: 400,
+                capacity: 10000,
+                can_extract: true,
+                can_receive: false,
+            })
+        );
+        assert!(decoded[1].tanks.is_empty());
+        assert_eq!(decoded[1].energy, None);
+    }
+
+    #[test]
+    fn test_item_durability_and_components() {
+        let mut pickaxe = item(0, "minecraft:iron_pickaxe", &[], 1);
+        pickaxe.max_damage = 250;
+        assert_eq!(pickaxe.durability(), Some(250));
+        assert!(!pickaxe.is_damaged());
+
+        pickaxe.damage = 40;
+        assert_eq!(pickaxe.durability(), Some(210));
+        assert!(pickaxe.is_damaged());
+
+        // a stack that can't be damaged ignores whatever damage it reports
+        let mut dirt = item(1, "minecraft:dirt", &[], 64);
+        dirt.damage = 3;
+        assert_eq!(dirt.durability(), None);
+        assert!(!dirt.is_damaged());
+
+        pickaxe
+            .components
+            .try_push(ScriptableComponent {
+                id: "minecraft:custom_data".to_string(),
+                value: "{owner:\"rose\"}".to_string(),
+            })
+            .unwrap();
+        assert_eq!(
+            pickaxe.component("minecraft:custom_data"),
+            Some("{owner:\"rose\"}".to_string())
+        );
+        assert_eq!(pickaxe.component("minecraft:lore"), None);
+    }
+
+    #[test]
+    fn test_value_codecs() {
+        let mut context = RuneheartContext::new().unwrap();
+        context
+            .set_active_script(SourceKind::Content(
+                r#"
+                pub fn tick() {
+                    let config = #{ name: "sorter", slots: [1, 2, 3], enabled: true };
+
+                    let json = rune::to_json(config)?;
+                    let from_json = rune::from_json(json)?;
+                    let from_cbor = rune::from_cbor(rune::to_cbor(config)?)?;
+
+                    Ok([
+                        from_json.name == "sorter",
+                        from_json.slots.len() == 3,
+                        from_cbor.enabled,
+                        rune::to_json(|| 1).is_err(),
+                        rune::from_json("{").is_err(),
+                    ])
+                }
+                "#
+                .to_string(),
+            ))
+            .unwrap();
+
+        let result = context.callback_tick_test().unwrap();
+        let result = rune::from_value::<Result<Vec<bool>, Value>>(result).unwrap();
+        assert_eq!(result.unwrap(), vec![true; 5]);
+    }
+
+    #[test]
+    fn test_direction_helpers() {
+        let mut context = RuneheartContext::new().unwrap();
+        context
+            .set_active_script(SourceKind::Content(
+                r#"
+                pub fn tick() {
+                    let north = rune::Direction::from_name("north").unwrap();
+
+                    [
+                        rune::Direction::all().len() == 6,
+                        north.opposite() == rune::Direction::South,
+                        north.rotate_clockwise(rune::Axis::Y) == rune::Direction::East,
+                        north.axis() == rune::Axis::Z,
+                        north.to_offset().z == -1,
+                        `${north}` == "north",
+                        `${north.axis()}` == "z",
+                    ]
+                }
+                "#
+                .to_string(),
+            ))
+            .unwrap();
+
+        let result = context.callback_tick_test().unwrap();
+        let result = rune::from_value::<Vec<bool>>(result).unwrap();
+        assert_eq!(result, vec![true; 7]);
+    }
 }
@@ -0,0 +1,149 @@
+use crate::script::rune_module::TransferError;
+use jni::sys::jint;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// tickets are unique across every context, so a ticket a script kept in its state from before its
+// context was recreated (chunk reload) can't name some other context's move. starting at the
+// launch time in micros keeps tickets saved by an earlier run of the server below every new one
+static NEXT_TICKET: LazyLock<AtomicU64> = LazyLock::new(|| {
+    let micros = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_micros() as u64);
+
+    AtomicU64::new(micros.max(1))
+});
+
+/// an item move recorded by `queue_move`. `src`/`dst` are indices into the raw entity array of
+/// the tick that queued it, the kotlin side resolves them against the same array when flushing
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct QueuedTransfer {
+    #[serde(skip)]
+    pub ticket: u64,
+    pub src: u32,
+    pub dst: u32,
+    pub slot: u32,
+    pub face: String,
+    pub amount: Option<i32>,
+}
+
+#[derive(Default)]
+struct Queue {
+    pending: Vec<QueuedTransfer>,
+    // results of the last flush, replaced on every flush
+    completed: BTreeMap<u64, Result<i32, TransferError>>,
+}
+
+/// transfers queued by scripts during a tick, flushed in one jni call once every script ran.
+/// clones share the same queue
+#[derive(Clone, Default)]
+pub struct TransferQueue(Rc<RefCell<Queue>>);
+
+impl TransferQueue {
+    pub fn push(&self, src: u32, dst: u32, slot: u32, face: &str, amount: Option<i32>) -> u64 {
+        let ticket = NEXT_TICKET.fetch_add(1, Ordering::Relaxed);
+        self.0.borrow_mut().pending.push(QueuedTransfer {
+            ticket,
+            src,
+            dst,
+            slot,
+            face: face.to_string(),
+            amount,
+        });
+
+        ticket
+    }
+
+    pub fn take_pending(&self) -> Vec<QueuedTransfer> {
+        std::mem::take(&mut self.0.borrow_mut().pending)
+    }
+
+    pub fn complete(&self, results: BTreeMap<u64, Result<i32, TransferError>>) {
+        self.0.borrow_mut().completed = results;
+    }
+
+    pub fn result(&self, ticket: u64) -> Option<Result<i32, TransferError>> {
+        self.0.borrow().completed.get(&ticket).cloned()
+    }
+}
+
+/// pairs the codes kotlin returned, one per move in queue order, with the tickets of `pending`.
+/// a failed call fails every move
+pub fn results(
+    pending: &[QueuedTransfer],
+    codes: Result<Vec<jint>, TransferError>,
+) -> BTreeMap<u64, Result<i32, TransferError>> {
+    pending
+        .iter()
+        .enumerate()
+        .map(|(i, transfer)| {
+            let result = match &codes {
+                Ok(codes) => codes.get(i).copied().map_or_else(
+                    || Err(TransferError::JniFailure("no result for queued move".to_string())),
+                    TransferError::from_code,
+                ),
+                Err(err) => Err(err.clone()),
+            };
+
+            (transfer.ticket, result)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue_three(queue: &TransferQueue) -> [u64; 3] {
+        [
+            queue.push(0, 1, 0, "up", None),
+            queue.push(0, 1, 1, "up", Some(4)),
+            queue.push(1, 0, 2, "down", None),
+        ]
+    }
+
+    #[test]
+    fn test_results_follow_queue_order() {
+        let queue = TransferQueue::default();
+        let tickets = queue_three(&queue);
+
+        let pending = queue.take_pending();
+        assert_eq!(pending.iter().map(|t| t.slot).collect::<Vec<_>>(), [0, 1, 2]);
+        assert!(queue.take_pending().is_empty());
+
+        // the last code is missing, as if kotlin returned a short array
+        queue.complete(results(&pending, Ok(vec![16, -2])));
+        assert_eq!(queue.result(tickets[0]), Some(Ok(16)));
+        assert_eq!(queue.result(tickets[1]), Some(Err(TransferError::DestinationFull)));
+        assert!(matches!(queue.result(tickets[2]), Some(Err(TransferError::JniFailure(_)))));
+    }
+
+    #[test]
+    fn test_tickets_are_unique_across_queues() {
+        let first = queue_three(&TransferQueue::default());
+        let second = queue_three(&TransferQueue::default());
+
+        assert!(first.iter().all(|ticket| !second.contains(ticket)));
+    }
+
+    #[test]
+    fn test_failed_flush_fails_every_move() {
+        let queue = TransferQueue::default();
+        let tickets = queue_three(&queue);
+
+        let failure = TransferError::JniFailure("boom".to_string());
+        queue.complete(results(&queue.take_pending(), Err(failure.clone())));
+        for ticket in tickets {
+            assert_eq!(queue.result(ticket), Some(Err(failure.clone())));
+        }
+
+        // a later flush replaces the results
+        queue.complete(BTreeMap::new());
+        assert_eq!(queue.result(tickets[0]), None);
+    }
+}
@@ -0,0 +1,124 @@
+use notify::{Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Receiver;
+use std::sync::{LazyLock, Mutex, mpsc};
+
+/// process wide watcher shared by every context, one notify watcher per block would quickly run
+/// into the inotify instance limit. every directory has a generation that is bumped whenever a
+/// `.rn` file in it changes, contexts compare it against the generation they last saw
+struct SharedWatcher {
+    // none if the watcher couldn't be created, scripts then just don't reload
+    watcher: Option<RecommendedWatcher>,
+    events: Receiver<notify::Result<Event>>,
+    directories: BTreeSet<PathBuf>,
+    generations: HashMap<PathBuf, u64>,
+}
+
+impl SharedWatcher {
+    fn drain_events(&mut self) {
+        let changed: Vec<PathBuf> = self
+            .events
+            .try_iter()
+            .flatten()
+            .filter(|event| matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)))
+            .flat_map(|event| event.paths)
+            .filter(|path| path.extension().is_some_and(|extension| extension == "rn"))
+            .filter_map(|path| path.parent().map(Path::to_path_buf))
+            .collect();
+
+        for directory in changed {
+            *self.generations.entry(directory).or_default() += 1;
+        }
+    }
+
+    fn generation(&self, directory: &Path) -> u64 {
+        self.generations.get(directory).copied().unwrap_or_default()
+    }
+}
+
+static SHARED_WATCHER: LazyLock<Mutex<SharedWatcher>> = LazyLock::new(|| {
+    // events go through a channel instead of being handled in the callback, the callback runs on
+    // notify's thread and taking the lock there could deadlock against `watch` waiting on it
+    let (tx, rx) = mpsc::channel();
+    let watcher = match RecommendedWatcher::new(tx, Config::default()) {
+        Ok(watcher) => Some(watcher),
+        Err(err) => {
+            eprintln!("failed to create script watcher: {:?}", err);
+            None
+        }
+    };
+
+    Mutex::new(SharedWatcher {
+        watcher,
+        events: rx,
+        directories: BTreeSet::new(),
+        generations: HashMap::new(),
+    })
+});
+
+/// a context's view of the shared watcher. the directory is watched instead of the file because
+/// most editors save by replacing the file, and so that edits to `mod` files are seen too.
+#[derive(Default)]
+pub struct ScriptWatcher {
+    // generation of each watched directory as of the last `changed_directories`
+    seen: BTreeMap<PathBuf, u64>,
+}
+
+impl ScriptWatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn watch(&mut self, script: &Path) -> notify::Result<()> {
+        let Some(directory) = script.parent() else {
+            return Ok(());
+        };
+
+        if self.seen.contains_key(directory) {
+            return Ok(());
+        }
+
+        let Ok(mut shared) = SHARED_WATCHER.lock() else {
+            return Ok(());
+        };
+
+        // changes from before this context watched the directory aren't its business
+        shared.drain_events();
+        self.seen
+            .insert(directory.to_path_buf(), shared.generation(directory));
+
+        if !shared.directories.contains(directory) {
+            let Some(watcher) = &mut shared.watcher else {
+                return Ok(());
+            };
+            watcher.watch(directory, RecursiveMode::NonRecursive)?;
+            shared.directories.insert(directory.to_path_buf());
+        }
+
+        Ok(())
+    }
+
+    /// directories that had a `.rn` file created or modified since the last call
+    pub fn changed_directories(&mut self) -> BTreeSet<PathBuf> {
+        if self.seen.is_empty() {
+            return BTreeSet::new();
+        }
+
+        let Ok(mut shared) = SHARED_WATCHER.lock() else {
+            return BTreeSet::new();
+        };
+
+        shared.drain_events();
+        self.seen
+            .iter_mut()
+            .filter_map(|(directory, seen)| {
+                let generation = shared.generation(directory);
+                (generation != *seen).then(|| {
+                    *seen = generation;
+                    directory.clone()
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,19 @@
+package rose.runeheart
+
+import kotlinx.serialization.ExperimentalSerializationApi
+import kotlinx.serialization.Serializable
+import kotlinx.serialization.cbor.Cbor
+import kotlinx.serialization.decodeFromByteArray
+
+// mirrors runelib's script::console::ConsoleLine
+@Serializable
+data class ConsoleLine(
+    val script: String,
+    val tick: Long,
+    // milliseconds since the unix epoch
+    val timestamp: Long,
+    val text: String,
+)
+
+@OptIn(ExperimentalSerializationApi::class)
+fun ByteArray.toConsoleLines(): List<ConsoleLine> = Cbor.decodeFromByteArray(this)
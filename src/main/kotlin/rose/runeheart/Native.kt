@@ -6,10 +6,26 @@ import rose.runeheart.blockentity.ExampleBlockEntity
 import rose.runeheart.blockentity.RawScriptableBlockEntity
 import java.io.File
 import java.nio.ByteBuffer
+import java.nio.file.Path
 
 typealias NativeContextHandle = Long;
 typealias NativeRenderContextHandle = Long;
 
+// ordinals must match Java_rose_runeheart_Native_createContext. TRUSTED gives scripts host i/o and
+// is only available when runelib is built with the `trusted` feature
+enum class CapabilityProfile {
+    SANDBOXED,
+    TRUSTED,
+}
+
+// ordinals must match Java_rose_runeheart_Native_getScriptState
+enum class ScriptState {
+    RUNNING,
+    SUSPENDED,
+    FINISHED,
+    QUARANTINED,
+}
+
 object Native {
     // TODO: clean this up
     init {
@@ -36,14 +52,75 @@ object Native {
 
 
     @JvmStatic
-    external fun createContext(): NativeContextHandle
+    external fun createContext(profile: Int): NativeContextHandle
 
     @JvmStatic
     external fun deleteContext(context: NativeContextHandle)
 
+    // instructions/deadlineMicros <= 0 means unlimited
+    @JvmStatic
+    external fun setExecutionBudget(context: NativeContextHandle, instructions: Long, deadlineMicros: Long)
+
+    // scripts are only loaded from disk (and hot reloaded) when they live under root
+    @JvmStatic
+    external fun setScriptsRoot(context: NativeContextHandle, root: String)
+
+    @JvmStatic
+    external fun saveScriptState(context: NativeContextHandle): ByteArray?
+
+    // true when the script state changed since the last call or since it was loaded
+    @JvmStatic
+    external fun takeScriptStateChanged(context: NativeContextHandle): Boolean
+
+    @JvmStatic
+    external fun loadScriptState(context: NativeContextHandle, state: ByteArray)
+
+    @JvmStatic
+    external fun addScript(context: NativeContextHandle, id: String, content: String)
+
+    @JvmStatic
+    external fun removeScript(context: NativeContextHandle, id: String): Boolean
+
+    @JvmStatic
+    external fun setScriptEnabled(context: NativeContextHandle, id: String, enabled: Boolean)
+
+    // cbor encoded list of ScriptDiagnostic from the last compile of the script
+    @JvmStatic
+    external fun getScriptDiagnostics(context: NativeContextHandle, id: String): ByteArray?
+
+    // cbor encoded ScriptError from the last failed tick of the script, null if it has not failed
+    @JvmStatic
+    external fun getLastScriptError(context: NativeContextHandle, id: String): ByteArray?
+
+    // cbor encoded ScriptError that got the script quarantined, null if it is not quarantined
+    @JvmStatic
+    external fun getQuarantineReason(context: NativeContextHandle, id: String): ByteArray?
+
+    // cbor encoded list of ConsoleLine printed by scripts since the last drain
+    @JvmStatic
+    external fun drainConsole(context: NativeContextHandle): ByteArray?
+
+    // -1 when the context has no script with that id, otherwise a ScriptState ordinal
+    @JvmStatic
+    external fun getScriptState(context: NativeContextHandle, id: String): Int
+
+    @JvmStatic
+    external fun reenableScript(context: NativeContextHandle, id: String)
+
+    // maxConsecutiveFailures <= 0 means scripts are never quarantined
+    @JvmStatic
+    external fun setFailurePolicy(context: NativeContextHandle, maxConsecutiveFailures: Int, backoff: Boolean, maxBackoffTicks: Int)
+
+    @JvmStatic
+    external fun tickScript(context: NativeContextHandle, id: String, obj: BlockEntity, raw: Array<RawScriptableBlockEntity>, scriptable: ByteArray)
+
     @JvmStatic
     external fun tick(context: NativeContextHandle, obj: BlockEntity, raw: Array<RawScriptableBlockEntity>, scriptable: ByteArray)
 
+    // runs on_unload of every script while the block entity is still around, call before deleteContext
+    @JvmStatic
+    external fun unloadScripts(context: NativeContextHandle, obj: BlockEntity, raw: Array<RawScriptableBlockEntity>)
+
     @JvmStatic
     external fun constructExampleBlockRenderData(context: NativeContextHandle): ByteArray
 
@@ -152,18 +229,124 @@ class RenderContext(val width: Int, val height: Int) : AutoCloseable {
     }
 }
 
-class ScriptContext() : AutoCloseable {
+class ScriptContext(val profile: CapabilityProfile = CapabilityProfile.SANDBOXED) : AutoCloseable {
     var handle: NativeContextHandle = 0;
 
     init {
         handle = try {
-            Native.createContext()
+            Native.createContext(profile.ordinal)
         } catch (e: RuntimeException) {
             LOGGER.error(e.message)
             0L
         }
     }
 
+    fun saveState(): ByteArray? {
+        if (handle == 0L) return null
+        return try {
+            Native.saveScriptState(handle)
+        } catch (e: RuntimeException) {
+            LOGGER.error(e.message)
+            null
+        }
+    }
+
+    fun takeStateChanged(): Boolean {
+        if (handle == 0L) return false
+        return try {
+            Native.takeScriptStateChanged(handle)
+        } catch (e: RuntimeException) {
+            LOGGER.error(e.message)
+            false
+        }
+    }
+
+    fun loadState(state: ByteArray) {
+        if (handle == 0L) return
+        try {
+            Native.loadScriptState(handle, state)
+        } catch (e: RuntimeException) {
+            LOGGER.error(e.message)
+        }
+    }
+
+    fun addScript(id: String, content: String): Boolean {
+        if (handle == 0L) return false
+        return try {
+            Native.addScript(handle, id, content)
+            true
+        } catch (e: RuntimeException) {
+            LOGGER.error(e.message)
+            false
+        }
+    }
+
+    fun removeScript(id: String): Boolean {
+        if (handle == 0L) return false
+        return Native.removeScript(handle, id)
+    }
+
+    fun setScriptEnabled(id: String, enabled: Boolean) {
+        if (handle == 0L) return
+        try {
+            Native.setScriptEnabled(handle, id, enabled)
+        } catch (e: RuntimeException) {
+            LOGGER.error(e.message)
+        }
+    }
+
+    fun getDiagnostics(id: String): List<ScriptDiagnostic> {
+        if (handle == 0L) return listOf()
+        return Native.getScriptDiagnostics(handle, id)?.toScriptDiagnostics() ?: listOf()
+    }
+
+    fun reenableScript(id: String) {
+        if (handle == 0L) return
+        try {
+            Native.reenableScript(handle, id)
+        } catch (e: RuntimeException) {
+            LOGGER.error(e.message)
+        }
+    }
+
+    fun setFailurePolicy(maxConsecutiveFailures: Int, backoff: Boolean, maxBackoffTicks: Int) {
+        if (handle != 0L) {
+            Native.setFailurePolicy(handle, maxConsecutiveFailures, backoff, maxBackoffTicks);
+        }
+    }
+
+    fun getLastError(id: String): ScriptError? {
+        if (handle == 0L) return null
+        return Native.getLastScriptError(handle, id)?.toScriptError()
+    }
+
+    fun getQuarantineReason(id: String): ScriptError? {
+        if (handle == 0L) return null
+        return Native.getQuarantineReason(handle, id)?.toScriptError()
+    }
+
+    fun drainConsole(): List<ConsoleLine> {
+        if (handle == 0L) return listOf()
+        return Native.drainConsole(handle)?.toConsoleLines() ?: listOf()
+    }
+
+    fun getScriptState(id: String): ScriptState? {
+        if (handle == 0L) return null
+        return ScriptState.entries.getOrNull(Native.getScriptState(handle, id))
+    }
+
+    fun setScriptsRoot(root: Path) {
+        if (handle != 0L) {
+            Native.setScriptsRoot(handle, root.toString())
+        }
+    }
+
+    fun setExecutionBudget(instructions: Long, deadlineMicros: Long) {
+        if (handle != 0L) {
+            Native.setExecutionBudget(handle, instructions, deadlineMicros);
+        }
+    }
+
     override fun close() {
         if (handle != 0L) {
             Native.deleteContext(handle);
@@ -0,0 +1,51 @@
+package rose.runeheart
+
+import kotlinx.serialization.ExperimentalSerializationApi
+import kotlinx.serialization.SerialName
+import kotlinx.serialization.Serializable
+import kotlinx.serialization.cbor.Cbor
+import kotlinx.serialization.decodeFromByteArray
+
+// mirrors runelib's script::diagnostics, names must match the serde output
+@Serializable
+enum class DiagnosticSeverity {
+    Error,
+    Warning,
+}
+
+@Serializable
+data class ScriptDiagnostic(
+    val severity: DiagnosticSeverity,
+    val message: String,
+    @SerialName("source_name") val sourceName: String,
+    // byte range into the script source
+    val span: List<Long>,
+    val line: Long,
+    val column: Long,
+    val notes: List<String>,
+)
+
+@OptIn(ExperimentalSerializationApi::class)
+fun ByteArray.toScriptDiagnostics(): List<ScriptDiagnostic> = Cbor.decodeFromByteArray(this)
+
+@Serializable
+data class StackFrame(
+    val function: String?,
+    @SerialName("source_name") val sourceName: String,
+    val span: List<Long>,
+    val line: Long,
+    val column: Long,
+)
+
+// mirrors runelib's script::diagnostics::ScriptError, frames are innermost first
+@Serializable
+data class ScriptError(
+    val message: String,
+    val frames: List<StackFrame>,
+) {
+    override fun toString(): String =
+        frames.firstOrNull()?.let { "line ${it.line}: $message" } ?: message
+}
+
+@OptIn(ExperimentalSerializationApi::class)
+fun ByteArray.toScriptError(): ScriptError = Cbor.decodeFromByteArray(this)
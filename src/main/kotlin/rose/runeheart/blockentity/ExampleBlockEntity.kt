@@ -4,22 +4,37 @@ import kotlinx.serialization.ExperimentalSerializationApi
 import kotlinx.serialization.SerialName
 import kotlinx.serialization.Serializable
 import kotlinx.serialization.cbor.Cbor
+import kotlinx.serialization.decodeFromByteArray
 import kotlinx.serialization.encodeToByteArray
 import net.minecraft.core.BlockPos
 import net.minecraft.core.Direction
+import net.minecraft.core.HolderLookup
+import net.minecraft.core.SectionPos
+import net.minecraft.core.component.DataComponents
 import net.minecraft.core.registries.BuiltInRegistries
+import net.minecraft.nbt.CompoundTag
+import net.minecraft.nbt.NbtOps
 import net.minecraft.network.chat.Component
 import net.minecraft.world.MenuProvider
 import net.minecraft.world.entity.player.Inventory
 import net.minecraft.world.entity.player.Player
 import net.minecraft.world.inventory.ContainerLevelAccess
+import net.minecraft.world.item.ItemStack
+import net.minecraft.world.item.enchantment.ItemEnchantments
 import net.minecraft.world.level.Level
+import net.minecraft.world.level.block.Block
 import net.minecraft.world.level.block.entity.BlockEntity
 import net.minecraft.world.level.block.state.BlockState
+import net.neoforged.fml.loading.FMLPaths
 import net.neoforged.neoforge.capabilities.Capabilities
+import net.neoforged.neoforge.fluids.capability.IFluidHandler
+import net.neoforged.neoforge.items.IItemHandler
 import rose.runeheart.Native
+import rose.runeheart.Runeheart.LOGGER
 import rose.runeheart.ScriptContext
 import rose.runeheart.menu.ExampleBlockMenu
+import java.util.IdentityHashMap
+import kotlin.math.abs
 
 
 // current ideas:
@@ -32,21 +47,132 @@ data class ScriptableItem(
     val name: String,
     val tags: List<String>,
     val count: Int,
+    @SerialName("max_stack_size") val maxStackSize: Int,
+    val damage: Int,
+    @SerialName("max_damage") val maxDamage: Int,
+    @SerialName("custom_name") val customName: String?,
+    val enchantments: List<ScriptableEnchantment>,
+    @SerialName("stored_enchantments") val storedEnchantments: List<ScriptableEnchantment>,
+    val components: List<ScriptableComponent>,
 )
 
+@Serializable
+data class ScriptableEnchantment(
+    val id: String,
+    val level: Int,
+)
+
+@Serializable
+data class ScriptableComponent(
+    val id: String,
+    // snbt, so scripts can read any component without us knowing its shape
+    val value: String,
+)
+
+fun ItemEnchantments.toScriptable(): List<ScriptableEnchantment> =
+    entrySet().map { ScriptableEnchantment(it.key.registeredName, it.intValue) }
+
+fun ItemStack.toScriptableItem(slotIndex: Int, registries: HolderLookup.Provider): ScriptableItem {
+    val ops = registries.createSerializationContext(NbtOps.INSTANCE)
+
+    return ScriptableItem(
+        slotIndex.toLong(),
+        BuiltInRegistries.ITEM.getKey(item).toString(),
+        itemHolder.tags().toList().map { it.location().toString() },
+        count,
+        maxStackSize,
+        damageValue,
+        maxDamage,
+        get(DataComponents.CUSTOM_NAME)?.string,
+        getOrDefault(DataComponents.ENCHANTMENTS, ItemEnchantments.EMPTY).toScriptable(),
+        getOrDefault(DataComponents.STORED_ENCHANTMENTS, ItemEnchantments.EMPTY).toScriptable(),
+        components.map { component ->
+            ScriptableComponent(
+                BuiltInRegistries.DATA_COMPONENT_TYPE.getKey(component.type()).toString(),
+                component.encodeValue(ops).result().map { it.toString() }.orElse(component.value().toString())
+            )
+        }
+    )
+}
+
 @Serializable
 data class ScriptableBlockEntity(
     @SerialName("raw_access_index") val rawAccessIndex: Long,
     @Serializable(with = BlockPosSerializer::class) @SerialName("block_pos") val blockPos: BlockPos,
     val dimension: String,
     val name: String,
-    val items: List<ScriptableItem>
+    val items: List<ScriptableItem>,
+    val slots: Int,
+    val tanks: List<ScriptableFluidTank>,
+    val energy: ScriptableEnergy?,
+)
+
+@Serializable
+data class ScriptableFluidTank(
+    @SerialName("tank_index") val tankIndex: Long,
+    val fluid: String,
+    val amount: Int,
+    val capacity: Int,
+)
+
+@Serializable
+data class ScriptableEnergy(
+    val stored: Int,
+    val capacity: Int,
+    @SerialName("can_extract") val canExtract: Boolean,
+    @SerialName("can_receive") val canReceive: Boolean,
+)
+
+// a move queued by a script with `queue_move`, src/dst index into rawScriptableEntities
+@Serializable
+data class QueuedTransfer(
+    val src: Long,
+    val dst: Long,
+    val slot: Int,
+    val face: String,
+    val amount: Int?,
 )
 
 data class RawScriptableBlockEntity(
     val blockEntity: BlockEntity,
 )
 
+fun BlockEntity.holdsItems(level: Level): Boolean =
+    level.getCapability(Capabilities.ItemHandler.BLOCK, blockPos, null)?.let {
+        (0..<it.slots).any { i -> !it.getStackInSlot(i).isEmpty }
+    } ?: false
+
+fun BlockEntity.toScriptable(rawAccessIndex: Int, level: Level): ScriptableBlockEntity = ScriptableBlockEntity(
+    rawAccessIndex.toLong(),
+    blockPos,
+    level.dimension().location().toString(),
+    BuiltInRegistries.BLOCK_ENTITY_TYPE.getKey(type).toString(),
+    level.getCapability(Capabilities.ItemHandler.BLOCK, blockPos, null)?.let {
+        (0..<it.slots).mapNotNull { i ->
+            val stack = it.getStackInSlot(i);
+            if (stack.isEmpty) {
+                return@mapNotNull null
+            }
+
+            stack.toScriptableItem(i, level.registryAccess())
+        }
+    } ?: listOf(),
+    level.getCapability(Capabilities.ItemHandler.BLOCK, blockPos, null)?.slots ?: 0,
+    level.getCapability(Capabilities.FluidHandler.BLOCK, blockPos, null)?.let {
+        (0..<it.tanks).map { i ->
+            val stack = it.getFluidInTank(i)
+            ScriptableFluidTank(
+                i.toLong(),
+                BuiltInRegistries.FLUID.getKey(stack.fluid).toString(),
+                stack.amount,
+                it.getTankCapacity(i)
+            )
+        }
+    } ?: listOf(),
+    level.getCapability(Capabilities.EnergyStorage.BLOCK, blockPos, null)?.let {
+        ScriptableEnergy(it.energyStored, it.maxEnergyStored, it.canExtract(), it.canReceive())
+    })
+
 @OptIn(ExperimentalSerializationApi::class)
 fun List<ScriptableBlockEntity>.toBytes(): ByteArray = Cbor.encodeToByteArray(this)
 
@@ -55,6 +181,8 @@ class ExampleBlockEntity(pos: BlockPos, state: BlockState) :
 
     var scriptContext: ScriptContext? = null;
     var renderData: ByteArray? = null;
+    // cbor encoded script state, kept around so it can be saved even before the context exists
+    var scriptState: ByteArray? = null;
     var rawScriptableEntities: List<RawScriptableBlockEntity> = listOf()
     var scriptableEntities: List<ScriptableBlockEntity> = listOf()
 
@@ -69,37 +197,190 @@ class ExampleBlockEntity(pos: BlockPos, state: BlockState) :
     // MOVE API./. NEEDS TO SUPPORT SIDES.
     // NEEDS TO SUPPORT FORGE TAGS TOO ON CHESTS MAYBE ??? omg
 
-    // TODO: need to decode scriptable item to get raw acecss index and stfuf
-    fun moveItem(srcRaw: RawScriptableBlockEntity, dstRaw: RawScriptableBlockEntity, itemSlotIndex: Int, face: Direction, amount: Int?) {
+    // transfers return how much moved, or one of the MOVE_* codes. keep in sync with TransferError::from_code
+    // with simulate set nothing is touched, the result is what a real move would have returned
+    fun moveItem(srcRaw: RawScriptableBlockEntity, dstRaw: RawScriptableBlockEntity, itemSlotIndex: Int, face: Direction, amount: Int?, simulate: Boolean): Int {
         val src = srcRaw.blockEntity;
         val dst = dstRaw.blockEntity;
 
-        val srcLevel = src.level ?: return
-        val dstLevel = dst.level ?: return
-        val srcPos = src.blockPos
-        val dstPos = dst.blockPos
+        val srcLevel = src.level ?: return MOVE_ENTITY_GONE
+        val dstLevel = dst.level ?: return MOVE_ENTITY_GONE
+        if (src.isRemoved || dst.isRemoved) return MOVE_ENTITY_GONE
 
         // ignore face on src we dont care
-        val srcHandler = srcLevel.getCapability(Capabilities.ItemHandler.BLOCK, srcPos, null) ?: return;
-        val dstHandler = dstLevel.getCapability(Capabilities.ItemHandler.BLOCK, dstPos, face) ?: return;
-
-        val src_stack = srcHandler.getStackInSlot(itemSlotIndex)
-        if (!src_stack.isEmpty) {
-            val stack = srcHandler.extractItem(itemSlotIndex, amount ?: src_stack.count, false);
-            run breaking@ {
-                (0..<dstHandler.slots).forEach { if (dstHandler.insertItem(it, stack, false).isEmpty) return@breaking }
+        val srcHandler = srcLevel.getCapability(Capabilities.ItemHandler.BLOCK, src.blockPos, null) ?: return MOVE_ENTITY_GONE;
+        val dstHandler = dstLevel.getCapability(Capabilities.ItemHandler.BLOCK, dst.blockPos, face) ?: return MOVE_ENTITY_GONE;
+
+        if (itemSlotIndex !in 0..<srcHandler.slots) return MOVE_NO_SUCH_SLOT
+        val srcStack = srcHandler.getStackInSlot(itemSlotIndex)
+        if (srcStack.isEmpty) return MOVE_NO_SUCH_SLOT
+
+        // find out how much the destination takes without touching anything, then move exactly that.
+        // extracting first and putting the rest back loses items in slots that don't accept inserts
+        val extractable = srcHandler.extractItem(itemSlotIndex, amount ?: srcStack.count, true)
+        if (extractable.isEmpty) return 0
+
+        val accepted = extractable.count - insertInto(dstHandler, extractable, true).count
+        if (accepted == 0) return MOVE_DESTINATION_FULL
+        if (simulate) return accepted
+
+        val extracted = srcHandler.extractItem(itemSlotIndex, accepted, false)
+        val remainder = insertInto(dstHandler, extracted, false)
+
+        // only a handler whose simulation lied gets here, never delete what it refused
+        if (!remainder.isEmpty) {
+            val leftover = srcHandler.insertItem(itemSlotIndex, remainder, false)
+            if (!leftover.isEmpty) Block.popResource(srcLevel, src.blockPos, leftover)
+        }
+
+        return extracted.count - remainder.count
+    }
+
+    // spreads stack over the handler's slots in order, returns what didn't fit
+    private fun insertInto(handler: IItemHandler, stack: ItemStack, simulate: Boolean): ItemStack {
+        var remainder = stack
+        for (slot in 0..<handler.slots) {
+            if (remainder.isEmpty) break
+            remainder = handler.insertItem(slot, remainder, simulate)
+        }
+
+        return remainder
+    }
+
+    // raw entities past the ones passed to Native.tick, i.e. ones found by queryBlockEntities this tick
+    fun rawScriptableEntity(index: Int): RawScriptableBlockEntity? = rawScriptableEntities.getOrNull(index)
+
+    // block entities within radius of this one matching name (a block entity type, null for any) and
+    // hasItems, closest first and at most limit of them. filtering happens before anything is serialized.
+    // they get raw indices after the ones already handed to scripts, so moves work on them until the next
+    // tick resets the list
+    fun queryBlockEntities(radius: Int, name: String?, hasItems: Boolean, limit: Int): ByteArray {
+        val level = level ?: return listOf<ScriptableBlockEntity>().toBytes()
+
+        val found = mutableListOf<BlockEntity>()
+        for (chunkX in SectionPos.blockToSectionCoord(blockPos.x - radius)..SectionPos.blockToSectionCoord(blockPos.x + radius)) {
+            for (chunkZ in SectionPos.blockToSectionCoord(blockPos.z - radius)..SectionPos.blockToSectionCoord(blockPos.z + radius)) {
+                // never load chunks for a script
+                val chunk = level.chunkSource.getChunkNow(chunkX, chunkZ) ?: continue
+                found += chunk.blockEntities.values.filter {
+                    it !== this &&
+                        abs(it.blockPos.x - blockPos.x) <= radius &&
+                        abs(it.blockPos.y - blockPos.y) <= radius &&
+                        abs(it.blockPos.z - blockPos.z) <= radius &&
+                        (name == null || BuiltInRegistries.BLOCK_ENTITY_TYPE.getKey(it.type).toString() == name) &&
+                        (!hasItems || it.holdsItems(level))
+                }
             }
         }
+
+        val raw = rawScriptableEntities.toMutableList()
+        val indices = IdentityHashMap<BlockEntity, Int>()
+        raw.forEachIndexed { i, it -> indices.putIfAbsent(it.blockEntity, i) }
+
+        val scriptable = found.sortedBy { it.blockPos.distSqr(blockPos) }.take(limit).map { entity ->
+            val index = indices.getOrPut(entity) { raw.size.also { raw += RawScriptableBlockEntity(entity) } }
+            entity.toScriptable(index, level)
+        }
+        rawScriptableEntities = raw
+
+        return scriptable.toBytes()
+    }
+
+    // runs every move scripts queued this tick, one result code per move in the same order
+    @OptIn(ExperimentalSerializationApi::class)
+    fun moveItems(bytes: ByteArray): IntArray {
+        val transfers = Cbor.decodeFromByteArray<List<QueuedTransfer>>(bytes)
+
+        return transfers.map {
+            val src = rawScriptableEntities.getOrNull(it.src.toInt()) ?: return@map MOVE_ENTITY_GONE
+            val dst = rawScriptableEntities.getOrNull(it.dst.toInt()) ?: return@map MOVE_ENTITY_GONE
+            moveItem(src, dst, it.slot, Direction.valueOf(it.face), it.amount, false)
+        }.toIntArray()
+    }
+
+    fun moveFluid(srcRaw: RawScriptableBlockEntity, dstRaw: RawScriptableBlockEntity, tankIndex: Int, face: Direction, amount: Int?): Int {
+        val src = srcRaw.blockEntity;
+        val dst = dstRaw.blockEntity;
+
+        val srcLevel = src.level ?: return MOVE_ENTITY_GONE
+        val dstLevel = dst.level ?: return MOVE_ENTITY_GONE
+        if (src.isRemoved || dst.isRemoved) return MOVE_ENTITY_GONE
+
+        val srcHandler = srcLevel.getCapability(Capabilities.FluidHandler.BLOCK, src.blockPos, null) ?: return MOVE_ENTITY_GONE;
+        val dstHandler = dstLevel.getCapability(Capabilities.FluidHandler.BLOCK, dst.blockPos, face) ?: return MOVE_ENTITY_GONE;
+
+        if (tankIndex !in 0..<srcHandler.tanks) return MOVE_NO_SUCH_SLOT
+        val tankStack = srcHandler.getFluidInTank(tankIndex)
+        if (tankStack.isEmpty) return 0
+
+        // same order as moveItem: simulate both ends, drain what the destination takes, then fill with what
+        // actually came out. filling first duplicates fluid whenever the real drain comes up short
+        val drainable = srcHandler.drain(tankStack.copyWithAmount(amount ?: tankStack.amount), IFluidHandler.FluidAction.SIMULATE)
+        if (drainable.isEmpty) return 0
+
+        val accepted = dstHandler.fill(drainable, IFluidHandler.FluidAction.SIMULATE)
+        if (accepted == 0) return MOVE_DESTINATION_FULL
+
+        val drained = srcHandler.drain(drainable.copyWithAmount(accepted), IFluidHandler.FluidAction.EXECUTE)
+        if (drained.isEmpty) return 0
+
+        val filled = dstHandler.fill(drained, IFluidHandler.FluidAction.EXECUTE)
+
+        // only a handler whose simulation lied gets here, hand back what the destination refused
+        if (filled < drained.amount) {
+            srcHandler.fill(drained.copyWithAmount(drained.amount - filled), IFluidHandler.FluidAction.EXECUTE)
+        }
+
+        return filled
+    }
+
+    fun moveEnergy(srcRaw: RawScriptableBlockEntity, dstRaw: RawScriptableBlockEntity, face: Direction, amount: Int?): Int {
+        val src = srcRaw.blockEntity;
+        val dst = dstRaw.blockEntity;
+
+        val srcLevel = src.level ?: return MOVE_ENTITY_GONE
+        val dstLevel = dst.level ?: return MOVE_ENTITY_GONE
+        if (src.isRemoved || dst.isRemoved) return MOVE_ENTITY_GONE
+
+        val srcStorage = srcLevel.getCapability(Capabilities.EnergyStorage.BLOCK, src.blockPos, null) ?: return MOVE_ENTITY_GONE;
+        val dstStorage = dstLevel.getCapability(Capabilities.EnergyStorage.BLOCK, dst.blockPos, face) ?: return MOVE_ENTITY_GONE;
+
+        // same order as moveFluid, never receive energy that hasn't been extracted yet
+        val extractable = srcStorage.extractEnergy(amount ?: Int.MAX_VALUE, true)
+        if (extractable == 0) return 0
+
+        val accepted = dstStorage.receiveEnergy(extractable, true)
+        if (accepted == 0) return MOVE_DESTINATION_FULL
+
+        val extracted = srcStorage.extractEnergy(accepted, false)
+        if (extracted == 0) return 0
+
+        val received = dstStorage.receiveEnergy(extracted, false)
+        if (received < extracted) {
+            srcStorage.receiveEnergy(extracted - received, false)
+        }
+
+        return received
     }
 
     fun test_get_data(): Int = 420
 
     companion object {
+        const val MOVE_NO_SUCH_SLOT = -1
+        const val MOVE_DESTINATION_FULL = -2
+        const val MOVE_ENTITY_GONE = -3
+
+        // under the game directory, only scripts in here are read from disk by the server
+        const val SCRIPTS_DIRECTORY = "runeheart_scripts"
+
         fun tick(level: Level, pos: BlockPos, state: BlockState, blockEntity: ExampleBlockEntity) {
             if (level.isClientSide) return;
 
             if (blockEntity.scriptContext == null) {
-                blockEntity.scriptContext = ScriptContext()
+                blockEntity.scriptContext = ScriptContext().also { context ->
+                    context.setScriptsRoot(FMLPaths.GAMEDIR.get().resolve(SCRIPTS_DIRECTORY))
+                    blockEntity.scriptState?.let { context.loadState(it) }
+                }
             }
 
             if (blockEntity.renderData != null) {
@@ -109,27 +390,7 @@ class ExampleBlockEntity(pos: BlockPos, state: BlockState) :
             blockEntity.rawScriptableEntities = blockEntity.getSurroundingBlockEntities(level, pos)
                 .mapNotNull { it.entity?.let { it1 -> RawScriptableBlockEntity(it1) } }
             blockEntity.scriptableEntities = blockEntity.rawScriptableEntities.mapIndexed { i, it ->
-                ScriptableBlockEntity(
-                    i.toLong(),
-                    it.blockEntity.blockPos,
-                    it.blockEntity.level!!.dimension().location().toString(),
-                    BuiltInRegistries.BLOCK_ENTITY_TYPE.getKey(it.blockEntity.type).toString(),
-                    level.getCapability(Capabilities.ItemHandler.BLOCK, it.blockEntity.blockPos, null)?.let {
-                        (0..<it.slots).mapNotNull { i ->
-                            val stack = it.getStackInSlot(i);
-                            if (stack.isEmpty) {
-                                return@mapNotNull null
-                            }
-
-                            ScriptableItem(
-                                i.toLong(),
-                                BuiltInRegistries.ITEM.getKey(stack.item).toString(),
-                                BuiltInRegistries.ITEM.getHolder(BuiltInRegistries.ITEM.getKey(stack.item))
-                                    .orElseThrow().tags().toList().map { item -> item.location().toString() },
-                                stack.count
-                            )
-                        }
-                    } ?: listOf())
+                it.blockEntity.toScriptable(i, level)
             }
 
             blockEntity.scriptContext?.let {
@@ -139,10 +400,58 @@ class ExampleBlockEntity(pos: BlockPos, state: BlockState) :
                     blockEntity.rawScriptableEntities.toTypedArray(),
                     blockEntity.scriptableEntities.toBytes()
                 );
+
+                // only dirty the chunk when a script actually wrote to its state
+                if (it.takeStateChanged()) {
+                    blockEntity.setChanged()
+                }
             }
         }
     }
 
+    override fun setRemoved() {
+        closeScriptContext()
+        super.setRemoved()
+    }
+
+    override fun onChunkUnloaded() {
+        closeScriptContext()
+        super.onChunkUnloaded()
+    }
+
+    // scripts get their on_unload while this block can still be reached, the state is kept after
+    // that (including what on_unload wrote) so a save after this still has it
+    private fun closeScriptContext() {
+        val context = scriptContext ?: return
+        scriptContext = null
+
+        if (context.handle != 0L) {
+            try {
+                Native.unloadScripts(context.handle, this, rawScriptableEntities.toTypedArray())
+            } catch (e: RuntimeException) {
+                LOGGER.error(e.message)
+            }
+        }
+        context.saveState()?.let { scriptState = it }
+        context.close()
+    }
+
+    override fun saveAdditional(tag: CompoundTag, registries: HolderLookup.Provider) {
+        super.saveAdditional(tag, registries)
+
+        scriptContext?.saveState()?.let { scriptState = it }
+        scriptState?.let { tag.putByteArray("script_state", it) }
+    }
+
+    override fun loadAdditional(tag: CompoundTag, registries: HolderLookup.Provider) {
+        super.loadAdditional(tag, registries)
+
+        if (tag.contains("script_state")) {
+            scriptState = tag.getByteArray("script_state")
+            scriptContext?.loadState(scriptState!!)
+        }
+    }
+
     fun updateRenderData(data: ByteArray) {
         renderData = data
     }